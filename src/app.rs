@@ -1,9 +1,10 @@
 use leptos::{IntoView, component, create_signal, view};
 use leptos_meta::{Link, Meta, Stylesheet, Title, provide_meta_context};
 use leptos_router::{A, Route, Router, Routes};
-use crate::components::{DiceRoller, RollHistoryPanel, SideNav};
+use crate::components::{DiceRoller, RollHistoryPanel, SideNav, TableRoller};
 use crate::layouts::Header;
 use crate::models::roll_history::provide_dice_history;
+use crate::models::variables::provide_variables;
 
 #[must_use]
 #[component]
@@ -18,6 +19,7 @@ pub fn App() -> impl IntoView {
     let (show_roll_history, set_show_roll_history) = create_signal(false);
     
     provide_dice_history();
+    provide_variables();
 
     view! {
         // injects a stylesheet into the document <head>
@@ -54,6 +56,7 @@ pub fn App() -> impl IntoView {
                 <Routes>
                     <Route path="" view=HomePage/>
                     <Route path="/dice" view=DiceRollerPage/>
+                    <Route path="/tables" view=RandomTablesPage/>
                     <Route path="/*any" view=NotFound/>
                 </Routes>
             </main>
@@ -74,6 +77,7 @@ fn HomePage() -> impl IntoView {
             <p>"A simple toolkit to help with your D&D sessions."</p>
             <div class="tool-links">
                 <A href="/dice">"Dice Roller"</A>
+                <A href="/tables">"Random Tables"</A>
             </div>
         </div>
     }
@@ -90,6 +94,17 @@ fn DiceRollerPage() -> impl IntoView {
     }
 }
 
+/// Random Tables page
+#[component]
+fn RandomTablesPage() -> impl IntoView {
+    view! {
+        <div>
+            <h2>"Random Tables"</h2>
+            <TableRoller />
+        </div>
+    }
+}
+
 /// 404 - Not Found
 #[component]
 fn NotFound() -> impl IntoView {
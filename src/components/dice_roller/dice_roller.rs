@@ -1,6 +1,6 @@
 use leptos::*;
 use crate::ui::{TabContainer, TabItem};
-use crate::components::dice_roller::{StandardRoller, ExpressionRoller};
+use crate::components::dice_roller::{StandardRoller, ExpressionRoller, TextRoller};
 
 #[component]
 pub fn DiceRoller() -> impl IntoView {
@@ -14,6 +14,10 @@ pub fn DiceRoller() -> impl IntoView {
             id: "solstora".to_string(), 
             title: "Solstora Calculator".to_string() 
         },
+        TabItem { 
+            id: "text".to_string(), 
+            title: "Roll from Text".to_string() 
+        },
     ];
     
     // Track the currently selected tab
@@ -39,6 +43,12 @@ pub fn DiceRoller() -> impl IntoView {
                         <ExpressionRoller />
                     </div>
                 </Show>
+                
+                <Show when=move || selected_tab.get() == "text">
+                    <div class="tab-content active">
+                        <TextRoller />
+                    </div>
+                </Show>
             </TabContainer>
         </div>
     }
@@ -1,29 +1,42 @@
 use leptos::*;
+use crate::models::dice::{Die, DieFaces};
 
 #[component]
 pub fn DieButton(
-    sides: u32,
+    die: Die,
     count: u32,
-    on_click: Callback<u32>,
+    on_click: Callback<Die>,
     is_active: bool,
 ) -> impl IntoView {
+    // Numbered dice get their own shape, anything else uses the d6 cube
+    let icon_class = match die.faces {
+        DieFaces::Numbered(sides) => format!("die-icon die-icon-d{}", sides),
+        DieFaces::Percentile => "die-icon die-icon-d100".to_string(),
+        _ => "die-icon die-icon-d6".to_string(),
+    };
+    let label_class = match die.faces {
+        DieFaces::Custom(_) => "dice-label dice-label-custom",
+        _ => "dice-label",
+    };
+    let label = die.to_string();
+
     let handle_click = move |_| {
-        // Emit the sides value to the parent component
-        on_click.call(sides);
+        // Emit the die to the parent component
+        on_click.call(die.clone());
     };
 
     let is_selected = move || count > 0;
 
     view! {
-        <button 
+        <button
             on:click=handle_click
             class:active=move || is_active
         >
-            <span class=format!("die-icon die-icon-d{}", sides)></span>
-            <span class="dice-label">{format!("d{}", sides)}</span>
+            <span class=icon_class></span>
+            <span class=label_class>{label}</span>
             <Show when=is_selected>
                 <span class="dice-count">{count}</span>
             </Show>
         </button>
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,231 @@
+use std::time::Duration;
+use leptos::*;
+use crate::models::dice::parse_dice_expression_with_variables;
+use crate::models::variables::use_variables;
+use crate::models::probability::Distribution;
+use crate::models::simulation::Simulation;
+
+// Number of rolls made by the "simulate" action
+const SIMULATION_RUNS: u64 = 100_000;
+
+// Rolls made per tick, small enough that the page stays responsive in between
+const SIMULATION_CHUNK: u64 = 5_000;
+
+// Formats a probability as a percentage, e.g. 0.1234 -> "12.3%"
+fn percent(probability: f64) -> String {
+    format!("{:.1}%", probability * 100.0)
+}
+
+// Most bars drawn in one chart; wider ranges of totals share a bar each
+const MAX_BARS: u64 = 100;
+
+// Bar chart of every possible total, scaled so the likeliest bar fills the height
+fn histogram(distribution: &Distribution) -> View {
+    let (min, max) = (i64::from(distribution.min()), i64::from(distribution.max()));
+    let width = ((max - min + 1) as u64).div_ceil(MAX_BARS) as i64;
+
+    let mut totals = vec![0.0; ((max - min) / width + 1) as usize];
+    for (value, p) in distribution.iter() {
+        totals[((i64::from(value) - min) / width) as usize] += p;
+    }
+
+    let highest = totals.iter().copied().fold(0.0, f64::max);
+    let bars = totals.into_iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let low = min + index as i64 * width;
+            let high = (low + width - 1).min(max);
+            let totals = if low == high { low.to_string() } else { format!("{}–{}", low, high) };
+            let height = if highest > 0.0 { p / highest * 100.0 } else { 0.0 };
+            view! {
+                <div class="distribution-bar" title=format!("{}: {}", totals, percent(p))>
+                    <div class="distribution-bar-fill" style=format!("height: {:.1}%", height)></div>
+                </div>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="distribution-bars">{bars}</div>
+        <div class="distribution-axis">
+            <span>{distribution.min()}</span>
+            <span>{distribution.max()}</span>
+        </div>
+    }.into_view()
+}
+
+// Rolls the next chunk, then schedules the one after it until the simulation is
+// complete. Stops early once `current` no longer matches, i.e. the expression changed.
+fn run_chunk(
+    simulation: RwSignal<Option<Simulation>>,
+    set_error: WriteSignal<String>,
+    current: StoredValue<u64>,
+    id: u64,
+) {
+    if current.get_value() != id {
+        return;
+    }
+
+    let mut finished = true;
+    simulation.update(|simulation| {
+        if let Some(simulation) = simulation {
+            let chunk = SIMULATION_CHUNK.min(SIMULATION_RUNS - simulation.runs());
+            match simulation.run(chunk) {
+                Ok(()) => finished = simulation.runs() >= SIMULATION_RUNS,
+                Err(err) => set_error.set(err.to_string()),
+            }
+        }
+    });
+
+    if !finished {
+        set_timeout(move || run_chunk(simulation, set_error, current, id), Duration::ZERO);
+    }
+}
+
+#[component]
+pub fn DistributionChart(
+    #[prop(into)] expression: Signal<String>,
+) -> impl IntoView {
+    let variables = use_variables().get_variables();
+    let parse = move |expression: &str| {
+        variables.with(|variables| parse_dice_expression_with_variables(expression, variables))
+    };
+
+    // Recomputed whenever the expression or a variable changes; empty input shows nothing
+    let distribution = create_memo(move |_| {
+        let expression = expression.get();
+        if expression.trim().is_empty() {
+            return None;
+        }
+        Some(
+            parse(&expression)
+                .map_err(|err| err.to_string())
+                .and_then(|parsed| Distribution::of_expression(&parsed).map_err(|err| err.to_string())),
+        )
+    });
+
+    // Whether the expression can be rolled at all, and so simulated
+    let parses = create_memo(move |_| parse(&expression.get()).is_ok());
+
+    // The "at least" target, left empty until the user picks one
+    let (target, set_target) = create_signal(String::new());
+    let target_value = move || target.get().trim().parse::<i32>().ok();
+
+    let simulation = create_rw_signal::<Option<Simulation>>(None);
+    let (simulation_error, set_simulation_error) = create_signal(String::new());
+
+    // Identifies the running simulation, so stale chunks stop once the expression or a variable changes
+    let simulation_id = store_value(0u64);
+
+    create_effect(move |_| {
+        expression.track();
+        variables.track();
+        simulation_id.update_value(|id| *id += 1);
+        simulation.set(None);
+        set_simulation_error.set(String::new());
+    });
+
+    let start_simulation = move |_| {
+        let Ok(parsed) = variables.with_untracked(|variables| {
+            parse_dice_expression_with_variables(&expression.get_untracked(), variables)
+        }) else {
+            return;
+        };
+        simulation_id.update_value(|id| *id += 1);
+        let id = simulation_id.get_value();
+        let seed = rand::random();
+        simulation.set(Some(Simulation::new(parsed, seed)));
+        set_simulation_error.set(String::new());
+        run_chunk(simulation, set_simulation_error, simulation_id, id);
+    };
+
+    let is_simulating = move || simulation.with(|simulation| {
+        simulation.as_ref().is_some_and(|simulation| simulation.runs() < SIMULATION_RUNS)
+    }) && simulation_error.get().is_empty();
+
+    view! {
+        {move || match distribution.get() {
+            None => ().into_view(),
+            // Parse errors already show up next to the roll button
+            Some(Err(err)) => view! { <div class="distribution-unavailable">{err}</div> }.into_view(),
+            Some(Ok(distribution)) => {
+                let summary = format!(
+                    "Mean {:.2} · Std dev {:.2} · Min {} · Max {} · Median {}",
+                    distribution.mean(),
+                    distribution.std_dev(),
+                    distribution.min(),
+                    distribution.max(),
+                    distribution.percentile(0.5),
+                );
+                let at_least = target_value()
+                    .map(|value| format!("P(≥ {}) = {}", value, percent(distribution.at_least(value))))
+                    .unwrap_or_default();
+
+                view! {
+                    <div class="distribution-chart">
+                        <div class="distribution-summary">{summary}</div>
+                        {histogram(&distribution)}
+                        <div class="distribution-target-odds">{at_least}</div>
+                    </div>
+                }.into_view()
+            }
+        }}
+
+        <Show when=move || parses.get()>
+            <div class="distribution-target">
+                <label>
+                    "At least: "
+                    <input
+                        type="number"
+                        prop:value=move || target.get()
+                        on:input=move |ev| set_target.set(event_target_value(&ev))
+                    />
+                </label>
+            </div>
+            <div class="roll-button-container">
+                <button
+                    on:click=start_simulation
+                    class="roll-button"
+                    disabled=is_simulating
+                >
+                    "Simulate 100k rolls"
+                </button>
+            </div>
+        </Show>
+
+        <Show when=move || !simulation_error.get().is_empty()>
+            <div class="error-message">
+                {move || simulation_error.get()}
+            </div>
+        </Show>
+
+        {move || simulation.with(|simulation| simulation.as_ref().map(|simulation| {
+            let distribution = simulation.distribution();
+            let summary = format!(
+                "Simulated {} of {} rolls (seed {}) · Mean {} · Std dev {:.2} · Min {} · Max {} · Median {}",
+                simulation.runs(),
+                SIMULATION_RUNS,
+                simulation.seed,
+                simulation.mean(),
+                simulation.std_dev(),
+                distribution.min(),
+                distribution.max(),
+                distribution.percentile(0.5),
+            );
+            let at_least = target_value()
+                .map(|value| {
+                    let odds = simulation.at_least(value);
+                    format!("P(≥ {}) ≈ {} ({} – {})", value, percent(odds.estimate), percent(odds.low), percent(odds.high))
+                })
+                .unwrap_or_default();
+
+            view! {
+                <div class="distribution-chart">
+                    <div class="distribution-summary">{summary}</div>
+                    {histogram(&distribution)}
+                    <div class="distribution-target-odds">{at_least}</div>
+                </div>
+            }
+        }))}
+    }
+}
@@ -1,10 +1,111 @@
 use leptos::*;
+use crate::models::dice::{parse_dice_expression_with_variables, DiceExpressionResult};
+use crate::models::roll_history::use_dice_history;
+use crate::models::variables::use_variables;
+use super::{DistributionChart, VariableEditor};
+use crate::components::RollTrace;
 
 #[component]
 pub fn ExpressionRoller() -> impl IntoView {
+    // The expression as typed by the user
+    let (expression, set_expression) = create_signal(String::new());
+
+    // Signal for any parse or evaluation error
+    let (error_msg, set_error_msg) = create_signal(String::new());
+
+    // Local signal to store the most recent roll, one result per repetition
+    let (last_roll, set_last_roll) = create_signal::<Vec<DiceExpressionResult>>(Vec::new());
+
+    let history_store = use_dice_history();
+    let variables = use_variables().get_variables();
+
+    let roll_expression = move || {
+        let rolled = variables.with(|variables| parse_dice_expression_with_variables(&expression.get(), variables))
+            .and_then(|parsed| parsed.roll_repeated());
+        match rolled {
+            Ok(results) => {
+                set_error_msg.set(String::new());
+                set_last_roll.set(results.clone());
+                // A repeated roll is kept together as a single history entry
+                history_store.add_roll(results);
+            }
+            Err(err) => {
+                set_error_msg.set(err.to_string());
+            }
+        }
+    };
+
     view! {
         <div class="expression-roller">
-            <p>"WIP: gets result of a roll expression, e.g. 5d12, 2d6+4, 2d20kh1, 3d20kl2"</p>
+            <div class="input-group">
+                <input
+                    type="text"
+                    class="expression-input"
+                    placeholder="e.g. 1d20+@str+@prof vs AC 15 # Longsword attack, 1d20+5 adv"
+                    prop:value=move || expression.get()
+                    on:input=move |ev| set_expression.set(event_target_value(&ev))
+                    on:keydown=move |ev| {
+                        if ev.key() == "Enter" {
+                            roll_expression();
+                        }
+                    }
+                />
+            </div>
+            // "8d6>=5" counts successes, while "1d20>=15" passes or fails like "1d20+5>=15"
+            <div class="expression-hint">
+                "A comparison after several dice counts successes, as in 8d6>=5. "
+                "After a single die or a total it passes or fails: 1d20>=15, 1d20+5 vs 15, 1d20 vs <12."
+            </div>
+
+            // Character values such as "@dex", usable in any expression
+            <VariableEditor />
+
+            <div class="roll-button-container">
+                <button
+                    on:click=move |_| roll_expression()
+                    class="roll-button"
+                    disabled=move || expression.get().trim().is_empty()
+                >
+                    "Roll!"
+                </button>
+            </div>
+
+            <Show when=move || !error_msg.get().is_empty()>
+                <div class="error-message">
+                    {move || error_msg.get()}
+                </div>
+            </Show>
+
+            <div class="last-roll-container">
+                <div class="last-roll-value">
+                    {move || {
+                        let totals: Vec<String> = last_roll.get().iter().map(|result| result.result.to_string()).collect();
+                        if totals.is_empty() { "-".to_string() } else { totals.join(", ") }
+                    }}
+                </div>
+                <div class="last-roll-outcomes">
+                    {move || last_roll.get().iter().filter_map(|result| result.outcome).map(|outcome| view! {
+                        <div
+                            class="last-roll-outcome"
+                            class:pass=outcome.success
+                            class:fail=!outcome.success
+                        >
+                            {outcome.to_string()}
+                        </div>
+                    }).collect_view()}
+                </div>
+                <Show when=move || last_roll.get().first().is_some_and(|result| result.description.is_some())>
+                    <div class="last-roll-label">
+                        {move || last_roll.get().first().and_then(|result| result.description.clone()).unwrap_or_default()}
+                    </div>
+                </Show>
+                {move || last_roll.get().iter().map(|result| view! {
+                    <div class="last-roll-details">{result.to_string()}</div>
+                    <RollTrace trace=result.trace() open=true />
+                }).collect_view()}
+            </div>
+
+            <DistributionChart expression=expression />
         </div>
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,16 @@
+#[allow(clippy::module_inception)]
 pub mod dice_roller;
 pub mod standard_roller;
 pub mod expression_roller;
 pub mod die_button;
+pub mod distribution_chart;
+pub mod variable_editor;
+pub mod text_roller;
 
 pub use dice_roller::DiceRoller;
 pub use standard_roller::StandardRoller;
 pub use expression_roller::ExpressionRoller;
-pub use die_button::DieButton;
\ No newline at end of file
+pub use die_button::DieButton;
+pub use distribution_chart::DistributionChart;
+pub use variable_editor::VariableEditor;
+pub use text_roller::TextRoller;
\ No newline at end of file
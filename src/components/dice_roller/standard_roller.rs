@@ -1,8 +1,9 @@
 use leptos::*;
-use crate::models::dice::{DiceRoll, DiceRollResult};
+use crate::models::dice::{Advantage, CompareOp, Comparison, DiceError, DiceExpressionResult, DiceRoll, DiceRollOp, Die, DieFaces, Target, TargetKind};
 use crate::models::roll_history::use_dice_history; // Add this import
 use std::collections::HashMap;
 use super::die_button::DieButton;
+use crate::components::RollTrace;
 
 // Common dice sides we support
 const COMMON_DICE: [u32; 7] = [20, 12, 10, 100, 8, 6, 4];
@@ -10,68 +11,114 @@ const COMMON_DICE: [u32; 7] = [20, 12, 10, 100, 8, 6, 4];
 #[component]
 pub fn StandardRoller() -> impl IntoView {
     // Map to store the count of each die type
-    let (dice_counts, set_dice_counts) = create_signal::<HashMap<u32, u32>>(HashMap::new());
+    let (dice_counts, set_dice_counts) = create_signal::<HashMap<Die, u32>>(HashMap::new());
+    
+    // Dice with non-standard faces, shown after the common dice. Fudge dice are always available.
+    let (custom_dice, set_custom_dice) = create_signal(vec![Die::fudge()]);
+    
+    // Face list typed by the user for a new custom die, e.g. "0,0,1,1,2,-1"
+    let (custom_faces, set_custom_faces) = create_signal(String::new());
     
     // Signal for any error message
     let (error_msg, set_error_msg) = create_signal(String::new());
     
     // Local signal to store the most recent roll result
-    let (last_roll, set_last_roll) = create_signal::<Option<DiceRollResult>>(None);
+    let (last_roll, set_last_roll) = create_signal::<Option<DiceExpressionResult>>(None);
+    
+    // Optional dice pool settings: count dice at or above the target as successes,
+    // and dice at or below the failure value as failures
+    let (success_target, set_success_target) = create_signal::<Option<i32>>(None);
+    let (failure_at, set_failure_at) = create_signal::<Option<i32>>(None);
+    
+    // Roll the d100 button as a tens die and a ones die, showing both in the result
+    let (percentile_d100, set_percentile_d100) = create_signal(true);
+    
+    // Lowest natural d20 that counts as a critical, e.g. 19 for a Champion. Empty means 20.
+    let (crit_from, set_crit_from) = create_signal::<Option<i32>>(None);
+    
+    // Optional DC or AC the total is checked against. Empty means no check.
+    let (target_value, set_target_value) = create_signal::<Option<i32>>(None);
+    let (target_kind, set_target_kind) = create_signal(TargetKind::Dc);
+    
+    // Advantage or disadvantage on a single d20. None is a normal roll.
+    let (advantage, set_advantage) = create_signal::<Option<Advantage>>(None);
     
     // Get the global roll history store from context
     let history_store = use_dice_history();
     
     // Function to increment a specific die
-    let increment_die = move |sides: u32| {
+    let increment_die = move |die: Die| {
         set_dice_counts.update(|counts| {
-            let count = counts.get(&sides).copied().unwrap_or(0);
-            counts.insert(sides, count + 1);
+            *counts.entry(die).or_insert(0) += 1;
         });
     };
     
+    // Adds a button for the typed face list, unless the same die is already there
+    let add_custom_die = move |_| {
+        let faces: Result<Vec<i32>, _> = custom_faces.get()
+            .split(',')
+            .map(|face| face.trim().parse::<i32>())
+            .collect();
+        
+        match faces {
+            Ok(faces) => match Die::custom(faces) {
+                Ok(die) => {
+                    set_error_msg.set(String::new());
+                    set_custom_dice.update(|dice| {
+                        if !dice.contains(&die) {
+                            dice.push(die);
+                        }
+                    });
+                    set_custom_faces.set(String::new());
+                }
+                Err(err) => set_error_msg.set(err.to_string()),
+            },
+            Err(_) => set_error_msg.set(String::from("Faces must be whole numbers separated by commas")),
+        }
+    };
+    
     // Check if any dice are selected
     let has_selection = move || {
         dice_counts.get().values().any(|&count| count > 0)
     };
     
-    // Create all dice rolls based on counts and return results
-    let create_dice_rolls = move || -> Option<(Vec<DiceRollResult>, DiceRollResult)> {
+    // Roll every selected die type as its own term, added together
+    let create_dice_rolls = move || -> Result<DiceExpressionResult, DiceError> {
         if !has_selection() {
-            return None;
+            return Err(DiceError::ZeroCount);
         }
         
-        let mut all_results = Vec::new();
-        let mut combined_dice_results = Vec::new();
-        let mut combined_total = 0;
+        let operations: Vec<DiceRollOp> = success_target.get()
+            .map(|target| DiceRollOp::CountSuccesses {
+                success: Comparison::new(CompareOp::GreaterOrEqual, target),
+                failure: failure_at.get().map(|value| Comparison::new(CompareOp::LessOrEqual, value)),
+            })
+            .into_iter()
+            .collect();
         
-        for (&sides, &count) in dice_counts.get().iter() {
-            if count > 0 {
-                let dice = DiceRoll::create(sides, count, None, Some(0));
-                let result = dice.roll();
-                
-                // Sum up the total
-                combined_total += result.result;
-                
-                // Collect all dice results
-                combined_dice_results.extend(result.dice_results.clone());
-                
-                all_results.push(result);
-            }
-        }
+        // Largest dice first, so the terms come out in the same order every time
+        let mut selected: Vec<(Die, u32)> = dice_counts.get().into_iter().filter(|(_, count)| *count > 0).collect();
+        selected.sort_by_key(|(die, _)| std::cmp::Reverse(die.max_face()));
         
-        if all_results.is_empty() {
-            return None;
+        let mut all_results = Vec::new();
+        for (die, count) in selected {
+            let mut operations = operations.clone();
+            if let (Some(crit_from), DieFaces::Numbered(20)) = (crit_from.get(), &die.faces) {
+                operations.push(DiceRollOp::Critical(Comparison::new(CompareOp::GreaterOrEqual, crit_from)));
+            }
+            let die = if percentile_d100.get() && die.faces == DieFaces::Numbered(100) {
+                Die::percentile()
+            } else {
+                die
+            };
+            let mut dice = DiceRoll::create(die, count, operations, None)?;
+            if let Some(advantage) = advantage.get() {
+                dice = dice.with_advantage(advantage)?;
+            }
+            all_results.push(dice.roll()?);
         }
         
-        // Create a combined result
-        let combined_result = DiceRollResult {
-            result: combined_total,
-            dice_results: combined_dice_results,
-            operation: None,
-            modifier: Some(0),
-        };
-        
-        Some((all_results, combined_result))
+        DiceExpressionResult::sum(all_results)
     };
     
     // Handle the roll action
@@ -84,48 +131,54 @@ pub fn StandardRoller() -> impl IntoView {
         }
         
         match create_dice_rolls() {
-            Some((individual_results, combined_result)) => {
-                set_last_roll.set(Some(combined_result.clone()));
+            Ok(mut result) => {
+                if let Some(value) = target_value.get() {
+                    result.check_against(Target::at_least(value, Some(target_kind.get())));
+                }
+                set_last_roll.set(Some(result.clone()));
                 
                 // Add the roll to history store
-                history_store.add_roll(vec![combined_result]);
+                history_store.add_roll(vec![result]);
                 
                 // Reset all die counts
                 set_dice_counts.set(HashMap::new());
             }
-            None => {
-                set_error_msg.set(String::from("Error creating dice rolls"));
+            Err(err) => {
+                set_error_msg.set(err.to_string());
             }
         }
     };
     
     // Function to get the count for a specific die
-    let get_die_count = move |sides: u32| -> u32 {
-        dice_counts.get().get(&sides).copied().unwrap_or(0)
+    let get_die_count = move |die: &Die| -> u32 {
+        dice_counts.get().get(die).copied().unwrap_or(0)
     };
     
     // Function to check if a die is selected (count > 0)
-    let is_die_selected = move |sides: u32| -> bool {
-        get_die_count(sides) > 0
+    let is_die_selected = move |die: &Die| -> bool {
+        get_die_count(die) > 0
     };
 
     // Create a callback for handling die button clicks
-    let die_callback = move |sides: u32| {
-        increment_die(sides);
+    let die_callback = move |die: Die| {
+        increment_die(die);
     };
 
     // Render dice buttons
     let render_dice_buttons = move || {
-        COMMON_DICE.iter().map(|&sides| {
-            view! {
-                <DieButton
-                    sides={sides}
-                    count={get_die_count(sides)}
-                    on_click={Callback::new(move |s| die_callback(s))}
-                    is_active={is_die_selected(sides)}
-                />
-            }
-        }).collect::<Vec<_>>()
+        COMMON_DICE.iter()
+            .map(|&sides| Die { faces: DieFaces::Numbered(sides) })
+            .chain(custom_dice.get())
+            .map(|die| {
+                view! {
+                    <DieButton
+                        count={get_die_count(&die)}
+                        is_active={is_die_selected(&die)}
+                        die={die}
+                        on_click={Callback::new(die_callback)}
+                    />
+                }
+            }).collect::<Vec<_>>()
     };
 
     view! {
@@ -135,6 +188,95 @@ pub fn StandardRoller() -> impl IntoView {
                 {render_dice_buttons}
             </div>
             
+            // Custom die faces, added as another button
+            <div class="input-group custom-die-settings">
+                <input
+                    type="text"
+                    placeholder="Custom faces, e.g. 0,0,1,1,2,-1"
+                    prop:value=move || custom_faces.get()
+                    on:input=move |ev| set_custom_faces.set(event_target_value(&ev))
+                />
+                <button
+                    on:click=add_custom_die
+                    disabled=move || custom_faces.get().trim().is_empty()
+                >
+                    "Add die"
+                </button>
+            </div>
+            
+            // Dice pool settings, leave the target empty to sum the dice instead
+            <div class="input-group dice-pool-settings">
+                <label>
+                    "Success on ≥ "
+                    <input
+                        type="number"
+                        min="1"
+                        placeholder="-"
+                        on:input=move |ev| set_success_target.set(event_target_value(&ev).parse().ok())
+                    />
+                </label>
+                <label>
+                    "Failure on ≤ "
+                    <input
+                        type="number"
+                        min="1"
+                        placeholder="-"
+                        disabled=move || success_target.get().is_none()
+                        on:input=move |ev| set_failure_at.set(event_target_value(&ev).parse().ok())
+                    />
+                </label>
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=move || percentile_d100.get()
+                        on:change=move |ev| set_percentile_d100.set(event_target_checked(&ev))
+                    />
+                    "d100 as tens + ones"
+                </label>
+                <label>
+                    "d20 crit on ≥ "
+                    <input
+                        type="number"
+                        min="1"
+                        max="20"
+                        placeholder="20"
+                        on:input=move |ev| set_crit_from.set(event_target_value(&ev).parse().ok())
+                    />
+                </label>
+                <label>
+                    "Target "
+                    <select
+                        on:change=move |ev| set_target_kind.set(match event_target_value(&ev).as_str() {
+                            "ac" => TargetKind::Ac,
+                            _ => TargetKind::Dc,
+                        })
+                    >
+                        <option value="dc" selected=move || target_kind.get() == TargetKind::Dc>"DC"</option>
+                        <option value="ac" selected=move || target_kind.get() == TargetKind::Ac>"AC"</option>
+                    </select>
+                    <input
+                        type="number"
+                        placeholder="-"
+                        on:input=move |ev| set_target_value.set(event_target_value(&ev).parse().ok())
+                    />
+                </label>
+            </div>
+            
+            // Rolls a lone d20 twice, keeping the higher or lower
+            <div class="advantage-toggle">
+                {[(Some(Advantage::Advantage), "Advantage"), (None, "Normal"), (Some(Advantage::Disadvantage), "Disadvantage")]
+                    .into_iter()
+                    .map(|(value, title)| view! {
+                        <button
+                            class:active=move || advantage.get() == value
+                            on:click=move |_| set_advantage.set(value)
+                        >
+                            {title}
+                        </button>
+                    })
+                    .collect_view()}
+            </div>
+            
             // Roll button
             <div class="roll-button-container">
                 <button 
@@ -155,12 +297,35 @@ pub fn StandardRoller() -> impl IntoView {
             
             // Always display the last roll container
             <div class="last-roll-container">
-                <div class="last-roll-value">
+                <div
+                    class="last-roll-value"
+                    class:critical=move || last_roll.get().is_some_and(|result| result.is_critical())
+                    class:fumble=move || last_roll.get().is_some_and(|result| result.is_fumble())
+                >
                     {move || last_roll.get().map(|result| result.result.to_string()).unwrap_or_else(|| "-".to_string())}
                 </div>
+                {move || last_roll.get().and_then(|result| result.outcome).map(|outcome| view! {
+                    <div
+                        class="last-roll-outcome"
+                        class:pass=outcome.success
+                        class:fail=!outcome.success
+                    >
+                        {outcome.to_string()}
+                    </div>
+                })}
+                <Show when=move || last_roll.get().is_some_and(|result| result.roll_results().iter().any(|roll| roll.is_dice_pool()))>
+                    <div class="last-roll-label">"net successes"</div>
+                </Show>
+                <Show when=move || last_roll.get().is_some_and(|result| result.is_critical())>
+                    <div class="last-roll-label critical">"natural critical"</div>
+                </Show>
+                <Show when=move || last_roll.get().is_some_and(|result| result.is_fumble())>
+                    <div class="last-roll-label fumble">"natural fumble"</div>
+                </Show>
                 <div class="last-roll-details">
                     {move || last_roll.get().map(|result| result.to_string()).unwrap_or_default()}
                 </div>
+                {move || last_roll.get().map(|result| view! { <RollTrace trace=result.trace() open=true /> })}
             </div>
         </div>
     }
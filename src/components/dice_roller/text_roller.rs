@@ -0,0 +1,100 @@
+use leptos::*;
+use crate::models::dice::{scan_inline_rolls, DiceExpression, DiceExpressionResult};
+use crate::models::roll_history::use_dice_history;
+use crate::components::RollTrace;
+
+#[component]
+pub fn TextRoller() -> impl IntoView {
+    // Text pasted by the user, e.g. a monster's attack description
+    let (text, set_text) = create_signal(String::new());
+
+    // Signal for any error from the last roll
+    let (error_msg, set_error_msg) = create_signal(String::new());
+
+    // Local signal to store the most recent roll result
+    let (last_roll, set_last_roll) = create_signal::<Option<DiceExpressionResult>>(None);
+
+    let history_store = use_dice_history();
+
+    let roll_dice = move |dice: DiceExpression| {
+        match dice.roll() {
+            Ok(result) => {
+                set_error_msg.set(String::new());
+                set_last_roll.set(Some(result.clone()));
+                history_store.add_roll(vec![result]);
+            }
+            Err(err) => set_error_msg.set(err.to_string()),
+        }
+    };
+
+    // The text with every roll in it turned into a button, the rest left as it is
+    let rendered_text = move || {
+        text.with(|text| {
+            let mut views = Vec::new();
+            let mut position = 0;
+            for inline in scan_inline_rolls(text) {
+                views.push(text[position..inline.start].to_string().into_view());
+                position = inline.end;
+
+                let view = match inline.roll {
+                    Ok(dice) => {
+                        let title = dice.to_string();
+                        view! {
+                            <button class="inline-roll" title=title on:click=move |_| roll_dice(dice.clone())>
+                                {inline.text}
+                            </button>
+                        }.into_view()
+                    }
+                    Err(err) => view! {
+                        <button class="inline-roll invalid" title=err.to_string() disabled=true>
+                            {inline.text}
+                        </button>
+                    }.into_view(),
+                };
+                views.push(view);
+            }
+            views.push(text[position..].to_string().into_view());
+            views.collect_view()
+        })
+    };
+
+    view! {
+        <div class="text-roller">
+            <div class="input-group">
+                <textarea
+                    class="text-roller-input"
+                    rows="4"
+                    placeholder="Paste text with rolls, e.g. Hit: 9 (1d10 + 4) slashing damage, or [[2d20kh1+5]]"
+                    prop:value=move || text.get()
+                    on:input=move |ev| set_text.set(event_target_value(&ev))
+                />
+            </div>
+
+            <Show when=move || !text.get().trim().is_empty()>
+                <div class="text-roller-output">
+                    {rendered_text}
+                </div>
+            </Show>
+
+            <Show when=move || !error_msg.get().is_empty()>
+                <div class="error-message">
+                    {move || error_msg.get()}
+                </div>
+            </Show>
+
+            <div class="last-roll-container">
+                <div
+                    class="last-roll-value"
+                    class:critical=move || last_roll.get().is_some_and(|result| result.is_critical())
+                    class:fumble=move || last_roll.get().is_some_and(|result| result.is_fumble())
+                >
+                    {move || last_roll.get().map(|result| result.result.to_string()).unwrap_or_else(|| "-".to_string())}
+                </div>
+                <div class="last-roll-details">
+                    {move || last_roll.get().map(|result| result.to_string()).unwrap_or_default()}
+                </div>
+                {move || last_roll.get().map(|result| view! { <RollTrace trace=result.trace() open=true /> })}
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,100 @@
+use leptos::*;
+use crate::models::dice::Variables;
+use crate::models::variables::use_variables;
+
+#[component]
+pub fn VariableEditor() -> impl IntoView {
+    let variable_store = use_variables();
+    let variables = variable_store.get_variables();
+
+    // The new variable as typed by the user, e.g. "dex" and "3"
+    let (new_name, set_new_name) = create_signal(String::new());
+    let (new_value, set_new_value) = create_signal(String::new());
+
+    let (error_msg, set_error_msg) = create_signal(String::new());
+
+    let add_variable = move |_| {
+        let name = new_name.get();
+        if !Variables::is_valid_name(&name) {
+            set_error_msg.set(String::from("Names may only use letters, digits and underscores"));
+            return;
+        }
+        match new_value.get().trim().parse::<i32>() {
+            Ok(value) => {
+                set_error_msg.set(String::new());
+                variable_store.set_variable(&name, value);
+                set_new_name.set(String::new());
+                set_new_value.set(String::new());
+            }
+            Err(_) => set_error_msg.set(String::from("Value must be a whole number")),
+        }
+    };
+
+    let variable_rows = move || {
+        variables.get()
+            .iter()
+            .map(|(name, value)| {
+                let name = name.to_string();
+                let update_name = name.clone();
+                let remove_name = name.clone();
+                view! {
+                    <div class="variable-row">
+                        <span class="variable-name">{format!("@{}", name)}</span>
+                        <input
+                            type="number"
+                            prop:value=value
+                            on:change=move |ev| {
+                                // Ignore anything that isn't a number, the input shows the old value again
+                                if let Ok(value) = event_target_value(&ev).trim().parse::<i32>() {
+                                    variable_store.set_variable(&update_name, value);
+                                }
+                            }
+                        />
+                        <button
+                            class="variable-remove"
+                            title="Remove variable"
+                            on:click=move |_| variable_store.remove_variable(&remove_name)
+                        >
+                            "×"
+                        </button>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="variable-editor">
+            <div class="variable-list">
+                {variable_rows}
+            </div>
+
+            <div class="input-group variable-settings">
+                <input
+                    type="text"
+                    placeholder="Variable, e.g. dex"
+                    prop:value=move || new_name.get()
+                    on:input=move |ev| set_new_name.set(event_target_value(&ev))
+                />
+                <input
+                    type="number"
+                    placeholder="Value"
+                    prop:value=move || new_value.get()
+                    on:input=move |ev| set_new_value.set(event_target_value(&ev))
+                />
+                <button
+                    on:click=add_variable
+                    disabled=move || new_name.get().trim().is_empty() || new_value.get().trim().is_empty()
+                >
+                    "Set"
+                </button>
+            </div>
+
+            <Show when=move || !error_msg.get().is_empty()>
+                <div class="error-message">
+                    {move || error_msg.get()}
+                </div>
+            </Show>
+        </div>
+    }
+}
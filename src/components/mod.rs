@@ -1,7 +1,11 @@
 pub mod side_nav;
 pub mod roll_history;
 pub mod dice_roller;
+pub mod roll_trace;
+pub mod table_roller;
 
 pub use side_nav::SideNav;
 pub use roll_history::RollHistoryPanel;
 pub use dice_roller::DiceRoller;
+pub use roll_trace::RollTrace;
+pub use table_roller::TableRoller;
@@ -1,6 +1,7 @@
 use leptos::*;
 use crate::ui::{SlidePanel, SlideDirection};
 use crate::models::roll_history::use_dice_history;
+use crate::components::RollTrace;
 
 #[component]
 pub fn RollHistoryPanel(
@@ -42,16 +43,42 @@ pub fn RollHistoryPanel(
                                 key=|entry| format!("{:?}", entry.timestamp.timestamp_millis())
                                 let:entry
                             >
-                                <div class="roll-result">
+                                <div
+                                    class="roll-result"
+                                    class:critical=entry.is_critical()
+                                    class:fumble=entry.is_fumble()
+                                >
                                     <span class="roll-time">{entry.timestamp.format("%H:%M:%S").to_string()}</span>
+                                    {entry.description.clone().map(|description| view! {
+                                        <span class="roll-description">{description}</span>
+                                    })}
+                                    {entry.table.clone().map(|table| view! {
+                                        <span class="roll-table">{table}</span>
+                                    })}
+                                    {entry.table_result.clone().map(|table_result| view! {
+                                        <span class="roll-table-result">{table_result}</span>
+                                    })}
+                                    {entry.outcomes().into_iter().map(|outcome| view! {
+                                        <span
+                                            class="roll-outcome"
+                                            class:pass=outcome.success
+                                            class:fail=!outcome.success
+                                        >
+                                            {if outcome.success { "PASS" } else { "FAIL" }}
+                                        </span>
+                                    }).collect_view()}
+                                    {entry.labels().into_iter().map(|label| view! {
+                                        <span class="roll-label">{label.to_string()}</span>
+                                    }).collect_view()}
                                     <span class="roll-details">
-                                        {move || {
-                                            entry.roll_results.iter()
-                                                .map(|r| r.to_string())
-                                                .collect::<Vec<_>>()
-                                                .join(", ")
-                                        }}
+                                        {entry.roll_results.iter()
+                                            .map(|r| r.to_string())
+                                            .collect::<Vec<_>>()
+                                            .join(", ")}
                                     </span>
+                                    {entry.roll_results.iter().map(|result| view! {
+                                        <RollTrace trace=result.trace() />
+                                    }).collect_view()}
                                 </div>
                             </For>
                         }.into_view()
@@ -0,0 +1,41 @@
+use leptos::*;
+use crate::models::dice::TraceNode;
+
+// Step-by-step breakdown of a roll as an expandable tree. Steps with
+// children open up into the steps that fed into them.
+#[component]
+pub fn RollTrace(
+    trace: TraceNode,
+    #[prop(optional)] open: bool,
+) -> impl IntoView {
+    view! {
+        <div class="roll-trace">
+            {trace_node(trace, open)}
+        </div>
+    }
+}
+
+// Returns a View rather than impl IntoView so it can call itself
+fn trace_node(node: TraceNode, open: bool) -> View {
+    // A constant is its own value, so "2" rather than "2 = 2"
+    let summary = match node.value {
+        Some(value) if value.to_string() != node.label => format!("{} = {}", node.label, value),
+        _ => node.label,
+    };
+
+    if node.children.is_empty() {
+        return view! { <div class="trace-leaf">{summary}</div> }.into_view();
+    }
+
+    // Only the top step starts open, the rest are one click away
+    let children = node.children.into_iter()
+        .map(|child| trace_node(child, false))
+        .collect_view();
+
+    view! {
+        <details class="trace-node" open=open>
+            <summary>{summary}</summary>
+            <div class="trace-children">{children}</div>
+        </details>
+    }.into_view()
+}
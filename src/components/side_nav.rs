@@ -24,6 +24,9 @@ pub fn SideNav(show_menu: ReadSignal<bool>, set_show_menu: WriteSignal<bool>) ->
                 <li>
                     <A href="/dice" on:click=close_menu>"Dice Roller"</A>
                 </li>
+                <li>
+                    <A href="/tables" on:click=close_menu>"Random Tables"</A>
+                </li>
                 // Add more navigation items as you develop more tools
             </ul>
         </SlidePanel>
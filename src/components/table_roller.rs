@@ -0,0 +1,129 @@
+use leptos::*;
+use crate::models::random_table::{TableRoll, TableSet};
+use crate::models::roll_history::use_dice_history;
+use crate::components::RollTrace;
+
+// Shown until the user pastes their own tables, as an example of the format
+const EXAMPLE_TABLES: &str = r#"[[tables]]
+name = "Road Encounters"
+dice = "1d12"
+
+[[tables.entries]]
+range = [1, 5]
+result = "Nothing but the wind"
+
+[[tables.entries]]
+range = [6, 8]
+result = "1d4+1 goblins looking for an easy mark"
+
+[[tables.entries]]
+range = [9, 11]
+result = "A merchant's cart with a broken wheel"
+roll_on = ["Trinkets"]
+
+[[tables.entries]]
+range = [12, 12]
+result = "2d6 wolves, hungry and bold"
+
+[[tables]]
+name = "Trinkets"
+
+[[tables.entries]]
+weight = 2
+result = "A tin whistle that only plays one note"
+
+[[tables.entries]]
+result = "A glass eye with a tiny crack"
+
+[[tables.entries]]
+result = "1d6 copper coins from a kingdom nobody remembers"
+"#;
+
+#[component]
+pub fn TableRoller() -> impl IntoView {
+    // JSON or TOML defining the tables, as typed or pasted by the user
+    let (definition, set_definition) = create_signal(EXAMPLE_TABLES.to_string());
+    let tables = create_memo(move |_| TableSet::parse(&definition.get()));
+
+    // Signal for any error from the last roll
+    let (error_msg, set_error_msg) = create_signal(String::new());
+
+    // Local signal to store the most recent table roll
+    let (last_roll, set_last_roll) = create_signal::<Option<TableRoll>>(None);
+
+    let history_store = use_dice_history();
+
+    let roll_table = move |name: String| {
+        let rolled = tables.with(|tables| match tables {
+            Ok(tables) => tables.roll(&name),
+            Err(err) => Err(err.clone()),
+        });
+        match rolled {
+            Ok(rolled) => {
+                set_error_msg.set(String::new());
+                history_store.add_table_roll(&rolled);
+                set_last_roll.set(Some(rolled));
+            }
+            Err(err) => set_error_msg.set(err.to_string()),
+        }
+    };
+
+    let table_buttons = move || {
+        match tables.get() {
+            Ok(tables) => tables.tables.into_iter()
+                .map(|table| {
+                    let name = table.name.clone();
+                    view! {
+                        <button on:click=move |_| roll_table(name.clone())>
+                            {table.name}
+                        </button>
+                    }
+                })
+                .collect_view(),
+            Err(err) => view! {
+                <div class="error-message">{err.to_string()}</div>
+            }.into_view(),
+        }
+    };
+
+    view! {
+        <div class="table-roller">
+            <div class="input-group">
+                <textarea
+                    class="table-definition"
+                    rows="12"
+                    spellcheck="false"
+                    prop:value=move || definition.get()
+                    on:input=move |ev| set_definition.set(event_target_value(&ev))
+                />
+            </div>
+
+            <div class="table-buttons">
+                {table_buttons}
+            </div>
+
+            <Show when=move || !error_msg.get().is_empty()>
+                <div class="error-message">
+                    {move || error_msg.get()}
+                </div>
+            </Show>
+
+            <div class="last-roll-container">
+                <div class="last-roll-value">
+                    {move || last_roll.get().map(|rolled| rolled.roll.result.to_string()).unwrap_or_else(|| "-".to_string())}
+                </div>
+                <Show when=move || last_roll.get().is_some()>
+                    <div class="last-roll-label">
+                        {move || last_roll.get().map(|rolled| rolled.table).unwrap_or_default()}
+                    </div>
+                </Show>
+                <div class="table-result">
+                    {move || last_roll.get().map(|rolled| rolled.full_result()).unwrap_or_default()}
+                </div>
+                {move || last_roll.get().map(|rolled| rolled.dice_results().into_iter().map(|result| view! {
+                    <RollTrace trace=result.trace() />
+                }).collect_view())}
+            </div>
+        </div>
+    }
+}
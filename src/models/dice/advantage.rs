@@ -0,0 +1,80 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::DiceRollOp;
+
+// Rolling a d20 more than once and keeping the best or worst, e.g. the "adv" in "1d20+5 adv"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Advantage {
+    Advantage, // Two d20s, keeping the higher
+    Disadvantage, // Two d20s, keeping the lower
+    ElvenAccuracy, // Three d20s, keeping the highest
+}
+
+impl Advantage {
+    // Longer words first, since keywords are matched as prefixes
+    pub const KEYWORDS: [(&'static str, Advantage); 5] = [
+        ("advantage", Advantage::Advantage),
+        ("adv", Advantage::Advantage),
+        ("disadvantage", Advantage::Disadvantage),
+        ("dis", Advantage::Disadvantage),
+        ("ea", Advantage::ElvenAccuracy),
+    ];
+
+    // Any amount of advantage and disadvantage cancel each other out, leaving
+    // a normal roll, however many of each there are
+    pub fn resolve(all: &[Advantage]) -> Option<Advantage> {
+        let disadvantage = all.contains(&Advantage::Disadvantage);
+        let elven_accuracy = all.contains(&Advantage::ElvenAccuracy);
+        let advantage = elven_accuracy || all.contains(&Advantage::Advantage);
+
+        match (advantage, disadvantage) {
+            (true, true) | (false, false) => None,
+            (false, true) => Some(Advantage::Disadvantage),
+            (true, false) if elven_accuracy => Some(Advantage::ElvenAccuracy),
+            (true, false) => Some(Advantage::Advantage),
+        }
+    }
+
+    // How many d20s are rolled in place of one
+    pub fn dice(&self) -> u32 {
+        match self {
+            Advantage::Advantage | Advantage::Disadvantage => 2,
+            Advantage::ElvenAccuracy => 3,
+        }
+    }
+
+    // Which of those d20s counts
+    pub fn keep(&self) -> DiceRollOp {
+        match self {
+            Advantage::Advantage | Advantage::ElvenAccuracy => DiceRollOp::KeepHighest(1),
+            Advantage::Disadvantage => DiceRollOp::KeepLowest(1),
+        }
+    }
+}
+
+impl fmt::Display for Advantage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Advantage::Advantage => write!(f, "adv"),
+            Advantage::Disadvantage => write!(f, "dis"),
+            Advantage::ElvenAccuracy => write!(f, "ea"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolving() {
+        let (adv, dis, ea) = (Advantage::Advantage, Advantage::Disadvantage, Advantage::ElvenAccuracy);
+        assert_eq!(Advantage::resolve(&[]), None);
+        assert_eq!(Advantage::resolve(&[adv, adv]), Some(adv));
+        assert_eq!(Advantage::resolve(&[dis]), Some(dis));
+        assert_eq!(Advantage::resolve(&[adv, dis, dis]), None);
+        assert_eq!(Advantage::resolve(&[ea, adv]), Some(ea));
+        assert_eq!(Advantage::resolve(&[ea, dis]), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareOp::Equal => write!(f, "="),
+            CompareOp::Less => write!(f, "<"),
+            CompareOp::LessOrEqual => write!(f, "<="),
+            CompareOp::Greater => write!(f, ">"),
+            CompareOp::GreaterOrEqual => write!(f, ">="),
+        }
+    }
+}
+
+// A condition on a single die face, e.g. the ">=9" in "4d10!>=9"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub op: CompareOp,
+    pub value: i32,
+}
+
+impl Comparison {
+    pub fn new(op: CompareOp, value: i32) -> Self {
+        Self { op, value }
+    }
+
+    pub fn matches(&self, value: i32) -> bool {
+        match self.op {
+            CompareOp::Equal => value == self.value,
+            CompareOp::Less => value < self.value,
+            CompareOp::LessOrEqual => value <= self.value,
+            CompareOp::Greater => value > self.value,
+            CompareOp::GreaterOrEqual => value >= self.value,
+        }
+    }
+
+    // True when every value in low..=high matches, e.g. ">=1" on any die
+    pub fn covers_range(&self, low: i32, high: i32) -> bool {
+        match self.op {
+            CompareOp::Equal => low == high && low == self.value,
+            CompareOp::Less => high < self.value,
+            CompareOp::LessOrEqual => high <= self.value,
+            CompareOp::Greater => low > self.value,
+            CompareOp::GreaterOrEqual => low >= self.value,
+        }
+    }
+}
+
+// Equality is written as the bare number, as in "r1" or "!9", unless the
+// number is negative: "r=-1" can't be mistaken for a subtraction
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            CompareOp::Equal if self.value >= 0 => write!(f, "{}", self.value),
+            op => write!(f, "{}{}", op, self.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_and_ranges() {
+        let at_least = Comparison::new(CompareOp::GreaterOrEqual, 5);
+        assert!(at_least.matches(5) && !at_least.matches(4));
+        assert!(at_least.covers_range(5, 10) && !at_least.covers_range(4, 10));
+        assert!(Comparison::new(CompareOp::Less, 3).covers_range(1, 2));
+        assert!(Comparison::new(CompareOp::Equal, 1).covers_range(1, 1));
+        assert!(!Comparison::new(CompareOp::Equal, 1).covers_range(1, 2));
+    }
+
+    #[test]
+    fn notation() {
+        assert_eq!(Comparison::new(CompareOp::Equal, 3).to_string(), "3");
+        assert_eq!(Comparison::new(CompareOp::Equal, -1).to_string(), "=-1");
+        assert_eq!(Comparison::new(CompareOp::LessOrEqual, 2).to_string(), "<=2");
+    }
+}
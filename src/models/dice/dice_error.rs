@@ -0,0 +1,82 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiceError {
+    ZeroSides,
+    ZeroCount,
+    KeepOutOfRange { keep: u32, die_count: u32 },
+    DropOutOfRange { drop: u32, die_count: u32 },
+    ExplodesOnEveryFace,
+    RerollsOnEveryFace,
+    Overflow,
+    DivisionByZero,
+    EmptyExpression,
+    UnexpectedCharacter { character: char, position: usize },
+    UnexpectedToken { token: String, position: usize },
+    UnexpectedEnd,
+    NumberTooLarge { position: usize },
+    NotASingleTerm,
+    UnknownVariable { name: String, position: usize },
+    RepeatOutOfRange { repeat: u32, max: u32 },
+    RepeatedRoll { repeat: u32 },
+    WrongArgumentCount { function: String, found: usize },
+    NotStreamable,
+    AdvantageWithoutD20,
+    ExpressionTooLong { length: usize, max: usize },
+    NestedTooDeeply { max: usize },
+    TooManyDice { max: u64 },
+    ExplosionChainTooLong { max: usize },
+    OutputTooLarge { max: usize },
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceError::ZeroSides => write!(f, "Die must have at least 1 side"),
+            DiceError::ZeroCount => write!(f, "Must roll at least 1 die"),
+            DiceError::KeepOutOfRange { keep, die_count } => {
+                write!(f, "Cannot keep {} of {} dice", keep, die_count)
+            },
+            DiceError::DropOutOfRange { drop, die_count } => {
+                write!(f, "Cannot drop {} of {} dice", drop, die_count)
+            },
+            DiceError::ExplodesOnEveryFace => write!(f, "Dice would explode on every face"),
+            DiceError::RerollsOnEveryFace => write!(f, "Dice would be rerolled on every face"),
+            DiceError::Overflow => write!(f, "Result is too large"),
+            DiceError::DivisionByZero => write!(f, "Division by zero"),
+            DiceError::EmptyExpression => write!(f, "Empty dice expression"),
+            DiceError::UnexpectedCharacter { character, position } => {
+                write!(f, "Unexpected character '{}' at position {}", character, position)
+            },
+            DiceError::UnexpectedToken { token, position } => {
+                write!(f, "Unexpected '{}' at position {}", token, position)
+            },
+            DiceError::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+            DiceError::NumberTooLarge { position } => write!(f, "Number too large at position {}", position),
+            DiceError::NotASingleTerm => write!(f, "Expected a single dice term"),
+            DiceError::UnknownVariable { name, position } => {
+                write!(f, "Unknown variable '@{}' at position {}", name, position)
+            },
+            DiceError::WrongArgumentCount { function, found } => {
+                write!(f, "{} can't take {} arguments", function, found)
+            },
+            DiceError::AdvantageWithoutD20 => write!(f, "Advantage and disadvantage need a single d20 to apply to"),
+            DiceError::NotStreamable => write!(f, "Only rolls without per-die operations can be summed as they are rolled"),
+            DiceError::ExpressionTooLong { length, max } => {
+                write!(f, "Expression is {} characters long, the limit is {}", length, max)
+            },
+            DiceError::NestedTooDeeply { max } => write!(f, "Expression is nested more than {} levels deep", max),
+            DiceError::TooManyDice { max } => write!(f, "Roll needs more than {} dice", max),
+            DiceError::ExplosionChainTooLong { max } => write!(f, "A die exploded more than {} times in a row", max),
+            DiceError::OutputTooLarge { max } => write!(f, "Result is longer than {} characters", max),
+            DiceError::RepeatOutOfRange { repeat, max } => {
+                write!(f, "Cannot repeat a roll {} times, the limit is {}", repeat, max)
+            },
+            DiceError::RepeatedRoll { repeat } => {
+                write!(f, "Roll is repeated {} times, so it has {} results rather than one", repeat, repeat)
+            },
+        }
+    }
+}
+
+impl std::error::Error for DiceError {}
@@ -0,0 +1,610 @@
+use std::borrow::Cow;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::{Advantage, DiceRoll, DiceRollOp, DiceError, Function, DiceRng, SeededDiceRng, ThreadDiceRng, DiceExpressionResult, DiceLimits, ExpressionNodeResult, RollBudget, Target, Variables};
+use super::parser;
+use super::tokenizer::escape_label;
+
+// Most times one expression can be rolled with "Nx", e.g. "6x 4d6dl1", by default; see DiceLimits
+pub const MAX_REPEAT: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide, // Integer division, rounding down as the 5e rules do
+}
+
+impl BinaryOp {
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Subtract => 1,
+            BinaryOp::Multiply | BinaryOp::Divide => 2,
+        }
+    }
+
+    pub fn apply(&self, lhs: i32, rhs: i32) -> Result<i32, DiceError> {
+        let value = match self {
+            BinaryOp::Add => lhs.checked_add(rhs),
+            BinaryOp::Subtract => lhs.checked_sub(rhs),
+            BinaryOp::Multiply => lhs.checked_mul(rhs),
+            BinaryOp::Divide => {
+                if rhs == 0 {
+                    return Err(DiceError::DivisionByZero);
+                }
+                lhs.checked_div(rhs).map(|q| {
+                    // checked_div truncates towards zero, so step down for negative quotients
+                    if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) { q - 1 } else { q }
+                })
+            },
+        };
+
+        value.ok_or(DiceError::Overflow)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Subtract => write!(f, "-"),
+            BinaryOp::Multiply => write!(f, "*"),
+            BinaryOp::Divide => write!(f, "/"),
+        }
+    }
+}
+
+// A node in the parsed expression tree
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpressionNode {
+    Roll(DiceRoll),
+    Constant(i32),
+    // A "@name" reference, with the value it resolved to when parsed
+    Variable {
+        name: String,
+        value: i32,
+    },
+    Negate(Box<ExpressionNode>),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<ExpressionNode>,
+        rhs: Box<ExpressionNode>,
+    },
+    // A labeled term that isn't a dice roll, e.g. "5[STR]" or "(1d4+1)[bless]".
+    // Dice rolls carry their label themselves.
+    Labeled {
+        inner: Box<ExpressionNode>,
+        label: String,
+    },
+    // Expressions rolled separately and then kept or dropped as a whole,
+    // e.g. "{1d20+5, 1d20+5}kh1". Without a keep or drop every item counts.
+    Group {
+        items: Vec<ExpressionNode>,
+        keep: Option<DiceRollOp>, // Only ever a keep or a drop
+    },
+    // e.g. "floor(8d6/2)" or "max(1, 1d4-1)"
+    Function {
+        function: Function,
+        args: Vec<ExpressionNode>,
+    },
+}
+
+impl ExpressionNode {
+    pub fn group(items: Vec<ExpressionNode>, keep: Option<DiceRollOp>) -> Result<Self, DiceError> {
+        if items.is_empty() {
+            return Err(DiceError::EmptyExpression);
+        }
+
+        let count = items.len() as u32;
+        match keep {
+            Some(DiceRollOp::KeepHighest(n) | DiceRollOp::KeepLowest(n)) if n == 0 || n > count => {
+                return Err(DiceError::KeepOutOfRange { keep: n, die_count: count });
+            },
+            Some(DiceRollOp::DropHighest(n) | DiceRollOp::DropLowest(n)) if n == 0 || n >= count => {
+                return Err(DiceError::DropOutOfRange { drop: n, die_count: count });
+            },
+            None | Some(DiceRollOp::KeepHighest(_) | DiceRollOp::KeepLowest(_)
+                | DiceRollOp::DropHighest(_) | DiceRollOp::DropLowest(_)) => {},
+            Some(_) => return Err(DiceError::NotASingleTerm),
+        }
+
+        Ok(ExpressionNode::Group { items, keep })
+    }
+
+    pub fn function(function: Function, args: Vec<ExpressionNode>) -> Result<Self, DiceError> {
+        if !function.accepts(args.len()) {
+            return Err(DiceError::WrongArgumentCount { function: function.to_string(), found: args.len() });
+        }
+        Ok(ExpressionNode::Function { function, args })
+    }
+
+    // Whether the expression has exactly one roll that advantage applies to, so
+    // "1d20+1d20 adv" can't quietly give both of them advantage
+    pub fn takes_advantage(&self) -> bool {
+        self.advantage_rolls() == 1
+    }
+
+    // How many rolls in the expression are a single d20 that advantage applies to
+    fn advantage_rolls(&self) -> usize {
+        match self {
+            ExpressionNode::Roll(dice) => usize::from(dice.takes_advantage()),
+            ExpressionNode::Constant(_) | ExpressionNode::Variable { .. } => 0,
+            ExpressionNode::Negate(inner) | ExpressionNode::Labeled { inner, .. } => inner.advantage_rolls(),
+            ExpressionNode::Binary { lhs, rhs, .. } => lhs.advantage_rolls() + rhs.advantage_rolls(),
+            ExpressionNode::Group { items, .. } | ExpressionNode::Function { args: items, .. } => {
+                items.iter().map(|item| item.advantage_rolls()).sum()
+            },
+        }
+    }
+
+    // Gives the d20 in the expression advantage, see DiceRoll::with_advantage.
+    // Check takes_advantage first, or every single d20 gets it.
+    pub fn with_advantage(&self, advantage: Advantage) -> Result<Self, DiceError> {
+        let with_advantage = |node: &ExpressionNode| node.with_advantage(advantage).map(Box::new);
+        let all_with_advantage = |nodes: &[ExpressionNode]| {
+            nodes.iter().map(|node| node.with_advantage(advantage)).collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match self {
+            ExpressionNode::Roll(dice) => ExpressionNode::Roll(dice.with_advantage(advantage)?),
+            ExpressionNode::Constant(_) | ExpressionNode::Variable { .. } => self.clone(),
+            ExpressionNode::Negate(inner) => ExpressionNode::Negate(with_advantage(inner)?),
+            ExpressionNode::Binary { op, lhs, rhs } => ExpressionNode::Binary {
+                op: *op,
+                lhs: with_advantage(lhs)?,
+                rhs: with_advantage(rhs)?,
+            },
+            ExpressionNode::Labeled { inner, label } => ExpressionNode::Labeled {
+                inner: with_advantage(inner)?,
+                label: label.clone(),
+            },
+            ExpressionNode::Group { items, keep } => ExpressionNode::Group {
+                items: all_with_advantage(items)?,
+                keep: keep.clone(),
+            },
+            ExpressionNode::Function { function, args } => ExpressionNode::Function {
+                function: *function,
+                args: all_with_advantage(args)?,
+            },
+        })
+    }
+
+    // Every die rolled is taken out of budget, failing once it runs out
+    pub fn roll_with<R: DiceRng + ?Sized>(&self, rng: &mut R, budget: &mut RollBudget) -> Result<ExpressionNodeResult, DiceError> {
+        match self {
+            ExpressionNode::Roll(dice) => Ok(ExpressionNodeResult::Roll(dice.roll_with_budget(rng, budget)?)),
+            ExpressionNode::Constant(value) => Ok(ExpressionNodeResult::Constant(*value)),
+            ExpressionNode::Variable { name, value } => Ok(ExpressionNodeResult::Variable {
+                name: name.clone(),
+                value: *value,
+            }),
+            ExpressionNode::Negate(inner) => {
+                let inner = inner.roll_with(rng, budget)?;
+                let value = inner.value().checked_neg().ok_or(DiceError::Overflow)?;
+                Ok(ExpressionNodeResult::Negate { inner: Box::new(inner), value })
+            },
+            ExpressionNode::Binary { op, lhs, rhs } => {
+                let lhs = lhs.roll_with(rng, budget)?;
+                let rhs = rhs.roll_with(rng, budget)?;
+                let value = op.apply(lhs.value(), rhs.value())?;
+                Ok(ExpressionNodeResult::Binary {
+                    op: *op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                    value,
+                })
+            },
+            ExpressionNode::Labeled { inner, label } => Ok(ExpressionNodeResult::Labeled {
+                inner: Box::new(inner.roll_with(rng, budget)?),
+                label: label.clone(),
+            }),
+            ExpressionNode::Group { items, keep } => {
+                let items = items.iter()
+                    .map(|item| item.roll_with(rng, budget))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let values: Vec<i32> = items.iter().map(|item| item.value()).collect();
+
+                let mut kept = vec![true; items.len()];
+                if let Some(keep) = keep {
+                    for index in keep.discarded(&values) {
+                        kept[index] = false;
+                    }
+                }
+
+                let value = values.iter()
+                    .zip(&kept)
+                    .filter(|(_, kept)| **kept)
+                    .try_fold(0i32, |total, (value, _)| total.checked_add(*value))
+                    .ok_or(DiceError::Overflow)?;
+
+                Ok(ExpressionNodeResult::Group { items, kept, keep: keep.clone(), value })
+            },
+            ExpressionNode::Function { function, args } => {
+                let args = args.iter()
+                    .map(|arg| arg.roll_with(rng, budget))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let value = match (function.rounding(), args.first()) {
+                    (Some(rounding), Some(arg)) => rounding.round(arg.exact()?)?,
+                    _ => function.apply(&args.iter().map(|arg| arg.value()).collect::<Vec<_>>())?,
+                };
+
+                Ok(ExpressionNodeResult::Function { function: *function, args, value })
+            },
+        }
+    }
+
+    // Binding strength used to decide where parentheses are needed when formatting
+    pub fn precedence(&self) -> u8 {
+        match self {
+            ExpressionNode::Binary { op, .. } => op.precedence(),
+            ExpressionNode::Negate(_) => 3,
+            ExpressionNode::Constant(value) if *value < 0 => 3,
+            // A built-in modifier is printed as a trailing "+N", which binds like an addition
+            ExpressionNode::Roll(dice) if dice.modifier.is_some_and(|m| m != 0) => 1,
+            ExpressionNode::Roll(_) | ExpressionNode::Constant(_)
+            | ExpressionNode::Variable { .. } | ExpressionNode::Labeled { .. }
+            | ExpressionNode::Group { .. } | ExpressionNode::Function { .. } => 4,
+        }
+    }
+}
+
+impl fmt::Display for ExpressionNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionNode::Roll(dice) => write!(f, "{}", dice),
+            ExpressionNode::Constant(value) => write!(f, "{}", value),
+            ExpressionNode::Variable { name, .. } => write!(f, "@{}", name),
+            ExpressionNode::Negate(inner) => {
+                if inner.precedence() < self.precedence() {
+                    write!(f, "-({})", inner)
+                } else {
+                    write!(f, "-{}", inner)
+                }
+            },
+            ExpressionNode::Binary { op, lhs, rhs } => {
+                // Operators are left associative, so the right side needs parentheses on equal precedence
+                if lhs.precedence() < op.precedence() {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                write!(f, "{}", op)?;
+                // A negative right operand is wrapped too, so "5-(-3)" doesn't come out as "5--3"
+                if rhs.precedence() <= op.precedence() || rhs.precedence() == 3 {
+                    write!(f, "({})", rhs)
+                } else {
+                    write!(f, "{}", rhs)
+                }
+            },
+            ExpressionNode::Labeled { inner, label } => {
+                if inner.precedence() < self.precedence() {
+                    write!(f, "({}){}", inner, escape_label(label))
+                } else {
+                    write!(f, "{}{}", inner, escape_label(label))
+                }
+            },
+            ExpressionNode::Group { items, keep } => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "{{{}}}", items.join(","))?;
+                match keep {
+                    Some(keep) => write!(f, "{}", keep),
+                    None => Ok(()),
+                }
+            },
+            ExpressionNode::Function { function, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", function, args.join(","))
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiceExpression {
+    pub root: ExpressionNode,
+    #[serde(default)]
+    pub description: Option<String>, // Free text after a "#", e.g. "Longsword attack"
+    #[serde(default)]
+    pub target: Option<Target>, // What the total is checked against, e.g. "vs DC 15"
+    #[serde(default)]
+    pub repeat: Option<u32>, // How many times the whole expression is rolled, e.g. 6 for "6x 4d6dl1"
+    #[serde(default)]
+    pub advantage: Option<Advantage>, // Applied to the one single d20, e.g. "1d20+5 adv"
+}
+
+impl DiceExpression {
+    pub fn from_expression(exp: &str) -> Result<Self, DiceError> {
+        parser::parse(exp)
+    }
+
+    pub fn from_expression_with_variables(exp: &str, variables: &Variables) -> Result<Self, DiceError> {
+        parser::parse_with_variables(exp, variables)
+    }
+
+    pub fn from_expression_with_limits(exp: &str, variables: &Variables, limits: &DiceLimits) -> Result<Self, DiceError> {
+        parser::parse_with_limits(exp, variables, limits)
+    }
+
+    // Just the one roll, with no target, description or repeat
+    pub fn from_roll(dice: DiceRoll) -> Self {
+        Self { root: ExpressionNode::Roll(dice), description: None, target: None, repeat: None, advantage: None }
+    }
+
+    // The root as it is rolled, with any advantage applied, e.g. "2d20kh1+5" for "1d20+5 adv"
+    pub fn root_with_advantage(&self) -> Result<Cow<'_, ExpressionNode>, DiceError> {
+        match self.advantage {
+            Some(_) if !self.root.takes_advantage() => Err(DiceError::AdvantageWithoutD20),
+            Some(advantage) => Ok(Cow::Owned(self.root.with_advantage(advantage)?)),
+            None => Ok(Cow::Borrowed(&self.root)),
+        }
+    }
+
+    pub fn roll(&self) -> Result<DiceExpressionResult, DiceError> {
+        self.roll_with(&mut ThreadDiceRng)
+    }
+
+    pub fn roll_with<R: DiceRng + ?Sized>(&self, rng: &mut R) -> Result<DiceExpressionResult, DiceError> {
+        self.roll_with_limits(rng, &DiceLimits::default())
+    }
+
+    // A single result, so "6x 4d6dl1" is refused rather than rolled once;
+    // roll_repeated gives one result per repetition
+    pub fn roll_with_limits<R: DiceRng + ?Sized>(&self, rng: &mut R, limits: &DiceLimits) -> Result<DiceExpressionResult, DiceError> {
+        if let Some(repeat) = self.repeat.filter(|&repeat| repeat > 1) {
+            return Err(DiceError::RepeatedRoll { repeat });
+        }
+        let result = self.roll_once(rng, &mut RollBudget::new(*limits))?;
+        limits.check_output_length(std::slice::from_ref(&result))?;
+        Ok(result)
+    }
+
+    // Rolls again exactly as the roll that recorded seed did. Each repetition
+    // has a seed of its own, so this replays one of them. Pass the limits the
+    // roll was made under, or a replay could get past limits the roll was held to.
+    pub fn replay(&self, seed: u64, limits: &DiceLimits) -> Result<DiceExpressionResult, DiceError> {
+        let result = self.roll_seeded(seed, &mut RollBudget::new(*limits))?;
+        limits.check_output_length(std::slice::from_ref(&result))?;
+        Ok(result)
+    }
+
+    // Each roll, repetitions included, gets a seed of its own, so the seed on
+    // a result replays just that roll
+    fn roll_once<R: DiceRng + ?Sized>(&self, rng: &mut R, budget: &mut RollBudget) -> Result<DiceExpressionResult, DiceError> {
+        match rng.next_seed() {
+            Some(seed) => self.roll_seeded(seed, budget),
+            None => self.roll_unseeded(rng, budget),
+        }
+    }
+
+    fn roll_seeded(&self, seed: u64, budget: &mut RollBudget) -> Result<DiceExpressionResult, DiceError> {
+        let result = self.roll_unseeded(&mut SeededDiceRng::new(seed), budget)?;
+        Ok(DiceExpressionResult { seed: Some(seed), ..result })
+    }
+
+    fn roll_unseeded<R: DiceRng + ?Sized>(&self, rng: &mut R, budget: &mut RollBudget) -> Result<DiceExpressionResult, DiceError> {
+        let root = self.root_with_advantage()?.roll_with(rng, budget)?;
+        let mut result = DiceExpressionResult::create(root);
+        result.description = self.description.clone();
+        if let Some(target) = self.target {
+            result.check_against(target);
+        }
+        Ok(result)
+    }
+
+    pub fn roll_repeated(&self) -> Result<Vec<DiceExpressionResult>, DiceError> {
+        self.roll_repeated_with(&mut ThreadDiceRng)
+    }
+
+    // One result per repetition, or just one without an "Nx"
+    pub fn roll_repeated_with<R: DiceRng + ?Sized>(&self, rng: &mut R) -> Result<Vec<DiceExpressionResult>, DiceError> {
+        self.roll_repeated_with_limits(rng, &DiceLimits::default())
+    }
+
+    // The repetitions share one budget, as max_dice counts every die the whole
+    // expression rolls: "100x 1000d6" needs 100,000 dice, not 1,000 a hundred times
+    pub fn roll_repeated_with_limits<R: DiceRng + ?Sized>(&self, rng: &mut R, limits: &DiceLimits) -> Result<Vec<DiceExpressionResult>, DiceError> {
+        let mut budget = RollBudget::new(*limits);
+        let results = (0..self.repeat.unwrap_or(1))
+            .map(|_| self.roll_once(rng, &mut budget))
+            .collect::<Result<Vec<_>, _>>()?;
+        limits.check_output_length(&results)?;
+        Ok(results)
+    }
+}
+
+impl fmt::Display for DiceExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(repeat) = self.repeat {
+            write!(f, "{}x ", repeat)?;
+        }
+        write!(f, "{}", self.root)?;
+        if let Some(advantage) = self.advantage {
+            write!(f, " {}", advantage)?;
+        }
+        if let Some(target) = &self.target {
+            write!(f, " {}", target)?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, " # {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::FixedSequenceRng;
+
+    #[test]
+    fn canonical_form_parses_back() {
+        for input in [
+            "1d20+5", "4d6kh3", "4d6dl1+2d8dh1", "4d6!>=5", "4d6!!", "10d10!p>=9", "8d6>=5f1",
+            "1d20r1", "2d6ro<3", "1d20min10", "4d6max5", "4dF", "d%", "1d{-1,0,0,1}",
+            "2d20cs>=19cf<=2", "3d6[fire]+2", "(1d4+1)[bless]", "5[STR]", "1d8[a\\]b]",
+            "{1d20+5, 1d20+5}kh1", "floor(8d6/2)", "max(1, 1d4-1)", "6x 4d6dl1",
+            "1d20+5 adv vs AC 15", "1d20 dis", "1d20 vs <12", "1d20 <= DC 12", "1d20+3 > 10 # Athletics",
+            "5-(-3)", "-(1d6+2)", "2*(1d6+1)/3",
+        ] {
+            let expression = DiceExpression::from_expression(input).unwrap();
+            let text = expression.to_string();
+            assert_eq!(DiceExpression::from_expression(&text), Ok(expression), "{} -> {}", input, text);
+        }
+    }
+
+    #[test]
+    fn labels_with_brackets_parse_back() {
+        let inner = ExpressionNode::Constant(5);
+        for label in ["a]b", "back\\slash", "[nested]"] {
+            let expression = DiceExpression::from_expression("1d20").unwrap();
+            let root = ExpressionNode::Binary {
+                op: BinaryOp::Add,
+                lhs: Box::new(expression.root),
+                rhs: Box::new(ExpressionNode::Labeled { inner: Box::new(inner.clone()), label: label.to_string() }),
+            };
+            let expression = DiceExpression { root, ..expression };
+            let text = expression.to_string();
+            assert_eq!(DiceExpression::from_expression(&text), Ok(expression), "{}", text);
+        }
+    }
+
+    #[test]
+    fn seeds_replay_their_own_roll() {
+        let expression = DiceExpression::from_expression("4d6dl1+1d8!").unwrap();
+        let result = expression.roll_with(&mut SeededDiceRng::new(42)).unwrap();
+        assert_eq!(expression.replay(result.seed.unwrap(), &DiceLimits::default()), Ok(result.clone()));
+        assert_eq!(expression.roll_with(&mut SeededDiceRng::new(42)), Ok(result.clone()));
+        assert!(expression.roll().unwrap().seed.is_some());
+
+        let limits = DiceLimits { max_dice: 4, ..DiceLimits::default() };
+        assert_eq!(expression.replay(result.seed.unwrap(), &limits), Err(DiceError::TooManyDice { max: 4 }));
+    }
+
+    #[test]
+    fn repetitions_get_seeds_of_their_own() {
+        let expression = DiceExpression::from_expression("6x 4d6dl1").unwrap();
+        let results = expression.roll_repeated_with(&mut SeededDiceRng::new(7)).unwrap();
+        assert_eq!(results.len(), 6);
+
+        let mut seeds: Vec<u64> = results.iter().map(|result| result.seed.unwrap()).collect();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), 6);
+        for result in results {
+            assert_eq!(expression.replay(result.seed.unwrap(), &DiceLimits::default()), Ok(result));
+        }
+    }
+
+    #[test]
+    fn single_rolls_refuse_repeats() {
+        let expression = DiceExpression::from_expression("6x 4d6dl1").unwrap();
+        assert_eq!(expression.roll_with(&mut SeededDiceRng::new(7)), Err(DiceError::RepeatedRoll { repeat: 6 }));
+        let once = DiceExpression::from_expression("1x 4d6dl1").unwrap();
+        assert!(once.roll_with(&mut SeededDiceRng::new(7)).is_ok());
+    }
+
+    #[test]
+    fn repetitions_share_one_dice_budget() {
+        let limits = DiceLimits { max_dice: 10, ..DiceLimits::default() };
+        let fits = DiceExpression::from_expression("2x 5d6").unwrap();
+        assert!(fits.roll_repeated_with_limits(&mut SeededDiceRng::new(1), &limits).is_ok());
+        let over = DiceExpression::from_expression("3x 5d6").unwrap();
+        assert_eq!(over.roll_repeated_with_limits(&mut SeededDiceRng::new(1), &limits), Err(DiceError::TooManyDice { max: 10 }));
+    }
+
+    #[test]
+    fn scripted_rolls_have_no_seed() {
+        let expression = DiceExpression::from_expression("2d6+1").unwrap();
+        let result = expression.roll_with(&mut FixedSequenceRng::new(vec![3, 4])).unwrap();
+        assert_eq!(result.result, 8);
+        assert_eq!(result.seed, None);
+    }
+
+    fn roll(expression: &str, values: &[u32]) -> Result<i32, DiceError> {
+        let expression = DiceExpression::from_expression(expression)?;
+        Ok(expression.roll_with(&mut FixedSequenceRng::new(values.to_vec()))?.result)
+    }
+
+    #[test]
+    fn arithmetic_and_precedence() {
+        assert_eq!(roll("1+2*3", &[]), Ok(7));
+        assert_eq!(roll("(1+2)*3", &[]), Ok(9));
+        assert_eq!(roll("10-4-3", &[]), Ok(3));
+        assert_eq!(roll("-2d6+1", &[3, 4]), Ok(-6));
+        assert_eq!(roll("2d6+1d4*2", &[3, 4, 2]), Ok(11));
+        assert_eq!(roll("1/0", &[]), Err(DiceError::DivisionByZero));
+    }
+
+    #[test]
+    fn floor_division() {
+        for (lhs, rhs, quotient) in [(7, 2, 3), (-7, 2, -4), (7, -2, -4), (-7, -2, 3), (-6, 2, -3), (0, 5, 0)] {
+            assert_eq!(BinaryOp::Divide.apply(lhs, rhs), Ok(quotient), "{}/{}", lhs, rhs);
+        }
+        assert_eq!(BinaryOp::Divide.apply(1, 0), Err(DiceError::DivisionByZero));
+        assert_eq!(BinaryOp::Divide.apply(i32::MIN, -1), Err(DiceError::Overflow));
+        assert_eq!(BinaryOp::Add.apply(i32::MAX, 1), Err(DiceError::Overflow));
+        assert_eq!(roll("-7/2", &[]), Ok(-4));
+    }
+
+    #[test]
+    fn rounding_functions() {
+        for (input, expected) in [
+            ("floor(7/2)", 3), ("ceil(7/2)", 4), ("round(7/2)", 4), ("round(5/2)", 3),
+            ("floor(-7/2)", -4), ("ceil(-7/2)", -3), ("round(-7/2)", -4), ("round(-5/3)", -2),
+            ("ceil((7/2)[half])", 4), ("abs(3-10)", 7), ("min(3, 1, 2)", 1), ("max(1, 1d4-1)", 1),
+        ] {
+            assert_eq!(roll(input, &[1]), Ok(expected), "{}", input);
+        }
+        // Everything inside a rounding function is exact, so it rounds only once
+        assert_eq!(roll("ceil(7/2+0)", &[]), Ok(4));
+        assert_eq!(roll("floor(7/2+7/2)", &[]), Ok(7));
+        assert_eq!(roll("round(-(1d6/4)*3)", &[3]), Ok(-2));
+        assert_eq!(roll("floor(1/(-1/2+1/2))", &[]), Err(DiceError::DivisionByZero));
+        assert!(matches!(DiceExpression::from_expression("abs(1, 2)"), Err(DiceError::WrongArgumentCount { .. })));
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(roll("{1d20+5, 1d20+5}kh1", &[7, 15]), Ok(20));
+        assert_eq!(roll("{1d20+5, 1d20+5}kl1", &[7, 15]), Ok(12));
+        assert_eq!(roll("{1d6, 1d6, 1d6}dl1", &[2, 5, 4]), Ok(9));
+        assert_eq!(roll("{1d6, 3}", &[2]), Ok(5));
+        assert!(matches!(DiceExpression::from_expression("{1d6, 1d6}kh3"), Err(DiceError::KeepOutOfRange { .. })));
+    }
+
+    #[test]
+    fn targets() {
+        let expression = DiceExpression::from_expression("1d20+5 vs DC 15").unwrap();
+        let result = expression.roll_with(&mut FixedSequenceRng::new(vec![12])).unwrap();
+        assert_eq!(result.outcome.map(|outcome| (outcome.success, outcome.margin)), Some((true, 2)));
+
+        let expression = DiceExpression::from_expression("1d20+5 vs AC 30").unwrap();
+        let result = expression.roll_with(&mut FixedSequenceRng::new(vec![20])).unwrap();
+        assert_eq!(result.outcome.map(|outcome| (outcome.success, outcome.automatic)), Some((true, true)));
+    }
+
+    #[test]
+    fn advantage_needs_exactly_one_d20() {
+        let mut expression = DiceExpression::from_expression("1d20+1d20").unwrap();
+        expression.advantage = Some(Advantage::Advantage);
+        assert_eq!(expression.roll_with(&mut FixedSequenceRng::new(vec![1])).err(), Some(DiceError::AdvantageWithoutD20));
+        assert_eq!(roll("1d20+1d4 adv", &[3, 17, 2]), Ok(19));
+    }
+
+    #[test]
+    fn a_comparison_after_dice_counts_successes() {
+        let expression = DiceExpression::from_expression("3d20>=15").unwrap();
+        assert!(expression.target.is_none());
+        assert_eq!(roll("3d20>=15", &[15, 2, 20]), Ok(2));
+
+        // After a single die it passes or fails instead
+        let expression = DiceExpression::from_expression("1d20 >= 15").unwrap();
+        assert_eq!(expression.target, Some(Target::at_least(15, None)));
+        let result = expression.roll_with(&mut FixedSequenceRng::new(vec![12])).unwrap();
+        assert_eq!((result.result, result.outcome.map(|outcome| outcome.success)), (12, Some(false)));
+    }
+}
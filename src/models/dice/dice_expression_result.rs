@@ -0,0 +1,388 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::{BinaryOp, DiceError, DiceRollOp, DiceRollResult, Fraction, Function, Target, TargetOutcome, TraceNode};
+
+// Evaluated counterpart of an ExpressionNode, keeping every intermediate value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpressionNodeResult {
+    Roll(DiceRollResult),
+    Constant(i32),
+    Variable {
+        name: String,
+        value: i32,
+    },
+    Negate {
+        inner: Box<ExpressionNodeResult>,
+        value: i32,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<ExpressionNodeResult>,
+        rhs: Box<ExpressionNodeResult>,
+        value: i32,
+    },
+    Labeled {
+        inner: Box<ExpressionNodeResult>,
+        label: String,
+    },
+    Group {
+        items: Vec<ExpressionNodeResult>,
+        kept: Vec<bool>, // One flag per item, false for those the keep or drop discarded
+        keep: Option<DiceRollOp>,
+        value: i32,
+    },
+    Function {
+        function: Function,
+        args: Vec<ExpressionNodeResult>,
+        value: i32,
+    },
+}
+
+impl ExpressionNodeResult {
+    pub fn value(&self) -> i32 {
+        match self {
+            ExpressionNodeResult::Roll(result) => result.result,
+            ExpressionNodeResult::Constant(value) => *value,
+            ExpressionNodeResult::Variable { value, .. } => *value,
+            ExpressionNodeResult::Negate { value, .. } => *value,
+            ExpressionNodeResult::Binary { value, .. } => *value,
+            ExpressionNodeResult::Labeled { inner, .. } => inner.value(),
+            ExpressionNodeResult::Group { value, .. } => *value,
+            ExpressionNodeResult::Function { value, .. } => *value,
+        }
+    }
+
+    // The exact value, with divisions, signs and arithmetic kept as fractions
+    // rather than rounded down, so a rounding function rounds only once
+    pub fn exact(&self) -> Result<Fraction, DiceError> {
+        match self {
+            ExpressionNodeResult::Negate { inner, .. } => inner.exact()?.negate(),
+            ExpressionNodeResult::Binary { op, lhs, rhs, .. } => lhs.exact()?.apply(*op, &rhs.exact()?),
+            ExpressionNodeResult::Labeled { inner, .. } => inner.exact(),
+            _ => Ok(Fraction::from(self.value())),
+        }
+    }
+
+    // Collect the results of every dice term, left to right, including
+    // discarded group items
+    pub fn roll_results(&self) -> Vec<&DiceRollResult> {
+        match self {
+            ExpressionNodeResult::Roll(result) => vec![result],
+            ExpressionNodeResult::Constant(_) | ExpressionNodeResult::Variable { .. } => Vec::new(),
+            ExpressionNodeResult::Negate { inner, .. } => inner.roll_results(),
+            ExpressionNodeResult::Binary { lhs, rhs, .. } => {
+                let mut results = lhs.roll_results();
+                results.extend(rhs.roll_results());
+                results
+            },
+            ExpressionNodeResult::Labeled { inner, .. } => inner.roll_results(),
+            ExpressionNodeResult::Group { items, .. } => items.iter().flat_map(|item| item.roll_results()).collect(),
+            ExpressionNodeResult::Function { args, .. } => args.iter().flat_map(|arg| arg.roll_results()).collect(),
+        }
+    }
+
+    // Like roll_results, but leaving out group items that were discarded
+    pub fn kept_roll_results(&self) -> Vec<&DiceRollResult> {
+        match self {
+            ExpressionNodeResult::Roll(result) => vec![result],
+            ExpressionNodeResult::Constant(_) | ExpressionNodeResult::Variable { .. } => Vec::new(),
+            ExpressionNodeResult::Negate { inner, .. } => inner.kept_roll_results(),
+            ExpressionNodeResult::Binary { lhs, rhs, .. } => {
+                let mut results = lhs.kept_roll_results();
+                results.extend(rhs.kept_roll_results());
+                results
+            },
+            ExpressionNodeResult::Labeled { inner, .. } => inner.kept_roll_results(),
+            ExpressionNodeResult::Group { items, kept, .. } => items.iter()
+                .zip(kept)
+                .filter(|(_, kept)| **kept)
+                .flat_map(|(item, _)| item.kept_roll_results())
+                .collect(),
+            ExpressionNodeResult::Function { args, .. } => args.iter().flat_map(|arg| arg.kept_roll_results()).collect(),
+        }
+    }
+
+    // Every inline label, left to right, whether on dice or on other terms
+    pub fn labels(&self) -> Vec<&str> {
+        match self {
+            ExpressionNodeResult::Roll(result) => result.label.as_deref().into_iter().collect(),
+            ExpressionNodeResult::Constant(_) | ExpressionNodeResult::Variable { .. } => Vec::new(),
+            ExpressionNodeResult::Negate { inner, .. } => inner.labels(),
+            ExpressionNodeResult::Binary { lhs, rhs, .. } => {
+                let mut labels = lhs.labels();
+                labels.extend(rhs.labels());
+                labels
+            },
+            ExpressionNodeResult::Labeled { inner, label } => {
+                let mut labels = vec![label.as_str()];
+                labels.extend(inner.labels());
+                labels
+            },
+            ExpressionNodeResult::Group { items, .. } => items.iter().flat_map(|item| item.labels()).collect(),
+            ExpressionNodeResult::Function { args, .. } => args.iter().flat_map(|arg| arg.labels()).collect(),
+        }
+    }
+
+    // Each arithmetic step with the values that went into it, e.g. "15 + 5"
+    pub fn trace(&self) -> TraceNode {
+        match self {
+            ExpressionNodeResult::Roll(result) => result.trace(),
+            ExpressionNodeResult::Constant(value) => TraceNode::leaf(value.to_string(), Some(*value)),
+            ExpressionNodeResult::Variable { name, value } => TraceNode::leaf(format!("@{}", name), Some(*value)),
+            ExpressionNodeResult::Negate { inner, value } => {
+                TraceNode::branch(format!("-({})", inner.value()), *value, vec![inner.trace()])
+            },
+            ExpressionNodeResult::Binary { op, lhs, rhs, value } => {
+                let label = format!("{} {} {}", lhs.value(), op, rhs.value());
+                TraceNode::branch(label, *value, vec![lhs.trace(), rhs.trace()])
+            },
+            ExpressionNodeResult::Labeled { inner, label } => inner.trace().labeled(label),
+            ExpressionNodeResult::Group { items, kept, keep, value } => {
+                let children = items.iter()
+                    .zip(kept)
+                    .map(|(item, kept)| {
+                        let trace = item.trace();
+                        if *kept { trace } else { TraceNode { label: format!("{} (discarded)", trace.label), ..trace } }
+                    })
+                    .collect();
+                let label = match keep {
+                    Some(keep) => format!("group, {}", keep.describe()),
+                    None => String::from("group"),
+                };
+                TraceNode::branch(label, *value, children)
+            },
+            ExpressionNodeResult::Function { function, args, value } => {
+                let values: Vec<String> = args.iter().map(|arg| arg.value().to_string()).collect();
+                let label = match args.first().map(|arg| arg.exact()) {
+                    Some(Ok(exact)) if function.rounding().is_some() && !exact.is_integer() => {
+                        format!("{}({} / {})", function, exact.numerator, exact.denominator)
+                    },
+                    _ => format!("{}({})", function, values.join(", ")),
+                };
+                TraceNode::branch(label, *value, args.iter().map(|arg| arg.trace()).collect())
+            },
+        }
+    }
+
+    // The breakdown followed by its total, unless it already reads as a single number
+    fn with_total(&self) -> String {
+        let single = match self {
+            ExpressionNodeResult::Roll(result) => {
+                result.dice_results.len() == 1 && result.operations.is_empty() && result.modifier.is_none_or(|m| m == 0)
+            },
+            ExpressionNodeResult::Constant(_) | ExpressionNodeResult::Variable { .. } => true,
+            _ => false,
+        };
+        if single { self.to_string() } else { format!("{} = {}", self, self.value()) }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            ExpressionNodeResult::Binary { op, .. } => op.precedence(),
+            ExpressionNodeResult::Negate { .. } => 3,
+            ExpressionNodeResult::Constant(value) if *value < 0 => 3,
+            ExpressionNodeResult::Roll(_) | ExpressionNodeResult::Constant(_)
+            | ExpressionNodeResult::Variable { .. } | ExpressionNodeResult::Labeled { .. }
+            | ExpressionNodeResult::Group { .. } | ExpressionNodeResult::Function { .. } => 4,
+        }
+    }
+}
+
+impl fmt::Display for ExpressionNodeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionNodeResult::Roll(result) => {
+                // A lone die reads fine on its own, anything longer is grouped so terms stay distinguishable
+                if result.dice_results.len() > 1 || !result.operations.is_empty() || result.modifier.is_some_and(|m| m != 0) {
+                    write!(f, "({})", result.breakdown())
+                } else {
+                    write!(f, "{}", result.breakdown())
+                }
+            },
+            ExpressionNodeResult::Constant(value) => write!(f, "{}", value),
+            // The name and what it stood for, e.g. "@dex (3)"
+            ExpressionNodeResult::Variable { name, value } => write!(f, "@{} ({})", name, value),
+            ExpressionNodeResult::Negate { inner, .. } => {
+                // "-(pen: 5)" rather than "-pen: 5"
+                if inner.precedence() < self.precedence() || matches!(**inner, ExpressionNodeResult::Labeled { .. }) {
+                    write!(f, "-({})", inner)
+                } else {
+                    write!(f, "-{}", inner)
+                }
+            },
+            ExpressionNodeResult::Binary { op, lhs, rhs, .. } => {
+                if lhs.precedence() < op.precedence() {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                write!(f, " {} ", op)?;
+                // A negative right operand is wrapped too, so "5-(-3)" doesn't come out as "5--3"
+                if rhs.precedence() <= op.precedence() || rhs.precedence() == 3 {
+                    write!(f, "({})", rhs)
+                } else {
+                    write!(f, "{}", rhs)
+                }
+            },
+            ExpressionNodeResult::Labeled { inner, label } => {
+                if inner.precedence() < self.precedence() {
+                    write!(f, "{}: ({})", label, inner)
+                } else {
+                    write!(f, "{}: {}", label, inner)
+                }
+            },
+            // "{[15] + 5 = 20, ([8] + 5 = 13: discarded)} (keeping highest 1)"
+            ExpressionNodeResult::Group { items, kept, keep, .. } => {
+                let items: Vec<String> = items.iter()
+                    .zip(kept)
+                    .map(|(item, kept)| {
+                        let shown = item.with_total();
+                        if *kept { shown } else { format!("({}: discarded)", shown) }
+                    })
+                    .collect();
+                write!(f, "{{{}}}", items.join(", "))?;
+                match keep {
+                    Some(keep) => write!(f, " ({})", keep.describe()),
+                    None => Ok(()),
+                }
+            },
+            // "floor(([3], [6]) / 2 = 4.5)", showing the exact value that gets rounded
+            ExpressionNodeResult::Function { function, args, .. } => {
+                let args: Vec<String> = args.iter()
+                    .map(|arg| match arg.exact() {
+                        Ok(exact) if function.rounding().is_some() && exact != Fraction::from(arg.value()) => {
+                            format!("{} = {}", arg, exact)
+                        },
+                        _ => arg.with_total(),
+                    })
+                    .collect();
+                write!(f, "{}({})", function, args.join(", "))
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiceExpressionResult {
+    pub result: i32,
+    pub root: ExpressionNodeResult,
+    pub seed: Option<u64>, // Replays this roll with DiceExpression::replay; None from scripted generators
+    #[serde(default)]
+    pub description: Option<String>, // Copied from the expression, e.g. "Longsword attack"
+    #[serde(default)]
+    pub target: Option<Target>,
+    #[serde(default)]
+    pub outcome: Option<TargetOutcome>, // Set together with target
+}
+
+impl DiceExpressionResult {
+    pub fn create(root: ExpressionNodeResult) -> Self {
+        Self {
+            result: root.value(),
+            root,
+            seed: None,
+            description: None,
+            target: None,
+            outcome: None,
+        }
+    }
+
+    // Separate rolls added together, e.g. a d20 and a d6 picked in the standard roller
+    pub fn sum(results: Vec<DiceRollResult>) -> Result<Self, DiceError> {
+        let mut terms = results.into_iter().map(ExpressionNodeResult::Roll);
+        let first = terms.next().ok_or(DiceError::ZeroCount)?;
+        let root = terms.try_fold(first, |lhs, rhs| {
+            let value = lhs.value().checked_add(rhs.value()).ok_or(DiceError::Overflow)?;
+            Ok(ExpressionNodeResult::Binary { op: BinaryOp::Add, lhs: Box::new(lhs), rhs: Box::new(rhs), value })
+        })?;
+        // A lone roll keeps its seed, as it would through From
+        let seed = match &root {
+            ExpressionNodeResult::Roll(result) => result.seed,
+            _ => None,
+        };
+        Ok(Self { seed, ..Self::create(root) })
+    }
+
+    // Checks the total against target, e.g. "vs AC 17", keeping the outcome on the result
+    pub fn check_against(&mut self, target: Target) {
+        self.outcome = Some(target.evaluate(self.result, self.is_critical(), self.is_fumble()));
+        self.target = Some(target);
+    }
+
+    pub fn roll_results(&self) -> Vec<&DiceRollResult> {
+        self.root.roll_results()
+    }
+
+    // The whole evaluation, from the total down to the individual dice
+    pub fn trace(&self) -> TraceNode {
+        let mut children = vec![self.root.trace()];
+        if let (Some(target), Some(outcome)) = (&self.target, &self.outcome) {
+            children.push(TraceNode::leaf(format!("{}: {}", target, outcome), None));
+        }
+
+        let label = self.description.clone().unwrap_or_else(|| String::from("total"));
+        TraceNode::branch(label, self.result, children)
+    }
+
+    pub fn labels(&self) -> Vec<&str> {
+        self.root.labels()
+    }
+
+    // Discarded group items don't count, so "{1d20, 1d20}kh1" is only critical if the kept d20 is
+    pub fn is_critical(&self) -> bool {
+        self.root.kept_roll_results().iter().any(|result| result.is_critical())
+    }
+
+    pub fn is_fumble(&self) -> bool {
+        self.root.kept_roll_results().iter().any(|result| result.is_fumble())
+    }
+}
+
+impl From<DiceRollResult> for DiceExpressionResult {
+    fn from(result: DiceRollResult) -> Self {
+        let seed = result.seed;
+        Self {
+            seed,
+            ..Self::create(ExpressionNodeResult::Roll(result))
+        }
+    }
+}
+
+impl fmt::Display for DiceExpressionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.root {
+            // A single term reads exactly like a plain DiceRollResult
+            ExpressionNodeResult::Roll(result) => write!(f, "{}", result)?,
+            root => write!(f, "Rolled: {} = {}", root, self.result)?,
+        }
+        if let (Some(target), Some(outcome)) = (&self.target, &self.outcome) {
+            write!(f, " {}: {}", target, outcome)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::dice::{DiceExpression, FixedSequenceRng};
+
+    #[test]
+    fn traces_follow_the_evaluation() {
+        let expression = DiceExpression::from_expression("2d6+3 # Damage").unwrap();
+        let trace = expression.roll_with(&mut FixedSequenceRng::new(vec![4, 5])).unwrap().trace();
+        assert_eq!((trace.label.as_str(), trace.value), ("Damage", Some(12)));
+        let sum = &trace.children[0];
+        assert_eq!((sum.label.as_str(), sum.value), ("9 + 3", Some(12)));
+        let labels: Vec<&str> = sum.children.iter().map(|child| child.label.as_str()).collect();
+        assert_eq!(labels, vec!["2d6", "3"]);
+    }
+
+    #[test]
+    fn rounding_shows_the_exact_division() {
+        let expression = DiceExpression::from_expression("floor(7/2)").unwrap();
+        let trace = expression.roll_with(&mut FixedSequenceRng::new(Vec::new())).unwrap().trace();
+        assert_eq!(trace.children[0].label, "floor(7 / 2)");
+        assert_eq!(trace.children[0].value, Some(3));
+    }
+}
@@ -0,0 +1,114 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Source of randomness for every roll in the dice model
+pub trait DiceRng {
+    // Returns a value in low..=high
+    fn roll_range(&mut self, low: u32, high: u32) -> u32;
+
+    // A fresh seed for one whole roll, which is then made with
+    // SeededDiceRng::new(seed), so the seed alone replays that roll. Scripted
+    // generators return None and have their values used as they come.
+    fn next_seed(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+// The default generator, backed by rand's thread-local RNG
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadDiceRng;
+
+impl DiceRng for ThreadDiceRng {
+    fn roll_range(&mut self, low: u32, high: u32) -> u32 {
+        rand::thread_rng().gen_range(low..=high)
+    }
+
+    fn next_seed(&mut self) -> Option<u64> {
+        Some(rand::thread_rng().gen())
+    }
+}
+
+// Deterministic generator: the same seed always produces the same rolls
+#[derive(Clone, Debug)]
+pub struct SeededDiceRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl SeededDiceRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    // Picks a random seed, so the rolls can be replayed later with SeededDiceRng::new
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    // The seed this generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl DiceRng for SeededDiceRng {
+    fn roll_range(&mut self, low: u32, high: u32) -> u32 {
+        self.rng.gen_range(low..=high)
+    }
+
+    // Drawn from this generator, so one seed still reproduces every roll made with it
+    fn next_seed(&mut self) -> Option<u64> {
+        Some(self.rng.gen())
+    }
+}
+
+// Scripted generator for tests: hands out the given values in order, starting
+// over once they run out. Values outside the requested range are clamped into it.
+#[derive(Clone, Debug)]
+pub struct FixedSequenceRng {
+    values: Vec<u32>,
+    index: usize,
+}
+
+impl FixedSequenceRng {
+    pub fn new(values: Vec<u32>) -> Self {
+        Self { values, index: 0 }
+    }
+}
+
+impl DiceRng for FixedSequenceRng {
+    fn roll_range(&mut self, low: u32, high: u32) -> u32 {
+        if self.values.is_empty() {
+            return low;
+        }
+        let value = self.values[self.index % self.values.len()];
+        self.index += 1;
+        value.clamp(low, high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_generators_repeat() {
+        let rolls = |seed| {
+            let mut rng = SeededDiceRng::new(seed);
+            (0..20).map(|_| rng.roll_range(1, 20)).collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(7), rolls(7));
+        assert_ne!(rolls(7), rolls(8));
+        assert!(rolls(7).iter().all(|roll| (1..=20).contains(roll)));
+    }
+
+    #[test]
+    fn fixed_sequences_cycle_and_clamp() {
+        let mut rng = FixedSequenceRng::new(vec![3, 9]);
+        assert_eq!((0..3).map(|_| rng.roll_range(1, 6)).collect::<Vec<_>>(), vec![3, 6, 3]);
+        assert_eq!(FixedSequenceRng::new(Vec::new()).roll_range(2, 5), 2);
+    }
+}
@@ -1,112 +1,239 @@
 use std::fmt;
-use regex::Regex;
-use super::{Die, DieRollResult, DiceRollResult, DiceRollOp};
+use serde::{Deserialize, Serialize};
+use super::{Advantage, CompareOp, Comparison, Die, DieFaces, DieRollResult, DiceRollResult, DiceRollOp, DiceError, DiceRng, SeededDiceRng, ThreadDiceRng, BinaryOp, DiceLimits, ExpressionNode, RollBudget, MAX_SHOWN_DICE};
+use super::parser;
+use super::tokenizer::escape_label;
 
 
+// Deserialized through create, so stored rolls get the same checks as parsed ones
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DiceRollFields")]
 pub struct DiceRoll {
     pub die: Die,
     pub die_count: u32,
-    pub operation: Option<DiceRollOp>,
+    pub operations: Vec<DiceRollOp>, // Stored in the order they are applied
     pub modifier: Option<i32>,
+    pub label: Option<String>, // Inline label, e.g. the "atk" in "1d20[atk]"
 }
 
 impl DiceRoll {
-    pub fn create(sides: u32, die_count: u32, operation: Option<DiceRollOp>, modifier: Option<i32>) -> Self {
-        assert!(sides > 0, "Die must have at least 1 side");
-        assert!(die_count > 0, "Must roll at least 1 die");
+    pub fn create(die: Die, die_count: u32, mut operations: Vec<DiceRollOp>, modifier: Option<i32>) -> Result<Self, DiceError> {
+        if die.sides() == 0 {
+            return Err(DiceError::ZeroSides);
+        }
+        if die_count == 0 {
+            return Err(DiceError::ZeroCount);
+        }
+
+        // "+0" is the same as no modifier, so both compare equal
+        let modifier = modifier.filter(|modifier| *modifier != 0);
         
-        // Check that operation values are valid if present
-        if let Some(op) = &operation {
+        // Put the operations into evaluation order; the sort is stable, so
+        // keeps and drops stay in the order they were written
+        operations.sort_by_key(|op| op.phase());
+
+        // Exploding on the highest face is what a bare "!" does, so "4d6!6" is
+        // stored the same way as "4d6!"
+        for op in &mut operations {
+            if let DiceRollOp::Explode { threshold, .. } = op {
+                if *threshold == Some(Comparison::new(CompareOp::Equal, die.max_face())) {
+                    *threshold = None;
+                }
+            }
+        }
+
+        // Largest distance any one die can land from zero, widened by clamps
+        let mut largest_face = match die.faces {
+            DieFaces::Numbered(sides) => i64::from(sides),
+            DieFaces::Percentile => 100,
+            _ => i64::from(die.min_face()).abs().max(i64::from(die.max_face()).abs()),
+        };
+
+        // Check that operation values are valid. Keeps and drops are checked
+        // against the dice left by earlier ones, ignoring any explosions.
+        let mut remaining = die_count;
+        for op in &operations {
             match op {
-                DiceRollOp::KeepHighest(n) => assert!(*n > 0, "KeepHighest must be at least 1"),
-                DiceRollOp::KeepLowest(n) => assert!(*n > 0, "KeepLowest must be at least 1"),
+                DiceRollOp::KeepHighest(n) | DiceRollOp::KeepLowest(n) => {
+                    if *n == 0 || *n > remaining {
+                        return Err(DiceError::KeepOutOfRange { keep: *n, die_count: remaining });
+                    }
+                    remaining = *n;
+                },
+                DiceRollOp::DropHighest(n) | DiceRollOp::DropLowest(n) => {
+                    if *n == 0 || *n >= remaining {
+                        return Err(DiceError::DropOutOfRange { drop: *n, die_count: remaining });
+                    }
+                    remaining -= *n;
+                },
+                DiceRollOp::Explode { threshold, .. } => {
+                    // A die that explodes on every face would only ever stop at the chain cap
+                    let always = match threshold {
+                        Some(threshold) => die.all_faces_match(threshold),
+                        None => die.min_face() == die.max_face(),
+                    };
+                    if always {
+                        return Err(DiceError::ExplodesOnEveryFace);
+                    }
+                },
+                DiceRollOp::Reroll { once: false, condition } => {
+                    if die.all_faces_match(condition) {
+                        return Err(DiceError::RerollsOnEveryFace);
+                    }
+                },
+                DiceRollOp::Minimum(n) | DiceRollOp::Maximum(n) => {
+                    largest_face = largest_face.max(i64::from(*n).abs());
+                },
+                DiceRollOp::Reroll { once: true, .. } | DiceRollOp::CountSuccesses { .. }
+                | DiceRollOp::Critical(_) | DiceRollOp::Fumble(_) => {},
             }
         }
+
+        // Reject anything whose largest possible total wouldn't fit in the result
+        let max_total = largest_face.checked_mul(i64::from(die_count))
+            .and_then(|total| total.checked_add(i64::from(modifier.unwrap_or(0)).abs()))
+            .ok_or(DiceError::Overflow)?;
+        if max_total > i64::from(i32::MAX) {
+            return Err(DiceError::Overflow);
+        }
         
-        Self {
-            die: Die { sides },
+        Ok(Self {
+            die,
             die_count,
-            operation,
+            operations,
             modifier,
-        }
+            label: None,
+        })
     }
 
-    pub fn from_expression(exp: &str) -> Result<Self, String> {
-        let exp = exp.trim();
-        // This regex captures: 
-        // 1. die count
-        // 2. die sides
-        // 3. optional keep operation (kh or kl) with its value
-        // 4. optional modifier with sign
-        let re = Regex::new(r"^(\d+)d(\d+)(?:(kh|kl)(\d+))?(?:([+-])(\d+))?$").map_err(|e| e.to_string())?;
-        
-        if let Some(caps) = re.captures(exp) {
-            let die_count = caps.get(1).unwrap().as_str().parse::<u32>()
-                .map_err(|_| "Invalid die count".to_string())?;
-                
-            let sides = caps.get(2).unwrap().as_str().parse::<u32>()
-                .map_err(|_| "Invalid die sides".to_string())?;
-                
-            // Parse operation if present
-            let operation = if caps.get(3).is_some() && caps.get(4).is_some() {
-                let op_type = caps.get(3).unwrap().as_str();
-                let op_value = caps.get(4).unwrap().as_str().parse::<u32>()
-                    .map_err(|_| "Invalid operation value".to_string())?;
-                    
-                match op_type {
-                    "kh" => Some(DiceRollOp::KeepHighest(op_value)),
-                    "kl" => Some(DiceRollOp::KeepLowest(op_value)),
-                    _ => None, // This shouldn't happen due to regex constraint
-                }
-            } else {
-                None
-            };
-            
-            // Parse modifier if present
-            let modifier = if caps.get(5).is_some() && caps.get(6).is_some() {
-                let sign = caps.get(5).unwrap().as_str();
-                let value = caps.get(6).unwrap().as_str().parse::<i32>()
-                    .map_err(|_| "Invalid modifier value".to_string())?;
-                    
-                match sign {
-                    "+" => Some(value),
-                    "-" => Some(-value),
-                    _ => None, // This shouldn't happen due to regex constraint
+    // Parses a single dice term such as "2d20kh1+5". Anything more involved
+    // (several terms, multiplication, parentheses) needs a DiceExpression.
+    pub fn from_expression(exp: &str) -> Result<Self, DiceError> {
+        let expression = parser::parse(exp)?;
+        if expression.target.is_some() || expression.repeat.is_some() {
+            return Err(DiceError::NotASingleTerm);
+        }
+
+        // "1d20+5 adv" is still a single term, as "2d20kh1+5"
+        match expression.root_with_advantage()?.into_owned() {
+            ExpressionNode::Roll(dice) => Ok(dice),
+            ExpressionNode::Binary { op: op @ (BinaryOp::Add | BinaryOp::Subtract), lhs, rhs } => {
+                match (*lhs, *rhs) {
+                    (ExpressionNode::Roll(dice), ExpressionNode::Constant(value)) if dice.modifier.is_none() => {
+                        let modifier = if op == BinaryOp::Add { value } else { -value };
+                        // Go through create again so the modifier is checked for overflow
+                        let label = dice.label;
+                        let folded = Self::create(dice.die, dice.die_count, dice.operations, Some(modifier))?;
+                        Ok(Self { label, ..folded })
+                    },
+                    _ => Err(DiceError::NotASingleTerm),
                 }
-            } else {
-                None
-            };
-            
-            // Use create to enforce validation
-            Ok(Self::create(sides, die_count, operation, modifier))
-        } else {
-            Err(format!("Failed to parse dice expression: {}", exp))
+            },
+            _ => Err(DiceError::NotASingleTerm),
+        }
+    }
+
+    // Whether rolling only needs a running total rather than every die,
+    // see DiceRollResult::stream
+    pub fn is_streamable(&self) -> bool {
+        self.die_count as usize > MAX_SHOWN_DICE
+            && self.operations.iter().all(|op| matches!(op, DiceRollOp::Critical(_) | DiceRollOp::Fumble(_)))
+    }
+
+    // Whether advantage applies to this roll, which takes a single d20 as in "1d20+5"
+    // A single d20 that doesn't already keep or drop, since "d20kh1 adv" would
+    // otherwise end up keeping twice
+    pub fn takes_advantage(&self) -> bool {
+        self.die_count == 1 && self.die.faces == DieFaces::Numbered(20) && !self.operations.iter().any(|op| matches!(
+            op,
+            DiceRollOp::KeepHighest(_) | DiceRollOp::KeepLowest(_) | DiceRollOp::DropHighest(_) | DiceRollOp::DropLowest(_)
+        ))
+    }
+
+    // The same roll with advantage, e.g. "1d20+5" becomes "2d20kh1+5". Anything
+    // that doesn't take advantage is left as it is.
+    pub fn with_advantage(&self, advantage: Advantage) -> Result<Self, DiceError> {
+        if !self.takes_advantage() {
+            return Ok(self.clone());
         }
+        // First among the keeps, so it picks the d20 before anything else narrows it down
+        let mut operations = self.operations.clone();
+        operations.insert(0, advantage.keep());
+        let roll = Self::create(self.die.clone(), advantage.dice(), operations, self.modifier)?;
+        Ok(Self { label: self.label.clone(), ..roll })
     }
 
-    pub fn roll(&self) -> DiceRollResult {
+    pub fn roll(&self) -> Result<DiceRollResult, DiceError> {
+        self.roll_with(&mut ThreadDiceRng)
+    }
+
+    // Rolled with a seed of its own, as DiceExpression rolls are
+    pub fn roll_with<R: DiceRng + ?Sized>(&self, rng: &mut R) -> Result<DiceRollResult, DiceError> {
+        match rng.next_seed() {
+            Some(seed) => self.replay(seed),
+            None => self.roll_with_budget(rng, &mut RollBudget::new(DiceLimits::default())),
+        }
+    }
+
+    // Rolls again exactly as the roll that recorded seed did
+    pub fn replay(&self, seed: u64) -> Result<DiceRollResult, DiceError> {
+        let result = self.roll_with_budget(&mut SeededDiceRng::new(seed), &mut RollBudget::new(DiceLimits::default()))?;
+        Ok(DiceRollResult { seed: Some(seed), ..result })
+    }
+
+    pub fn roll_with_budget<R: DiceRng + ?Sized>(&self, rng: &mut R, budget: &mut RollBudget) -> Result<DiceRollResult, DiceError> {
+        budget.spend(u64::from(self.die_count))?;
+
+        // Big pools that only sum their dice don't need every die kept around
+        if self.is_streamable() {
+            let result = DiceRollResult::stream(&self.die, self.die_count, self.operations.clone(), self.modifier, rng)?;
+            return Ok(DiceRollResult { label: self.label.clone(), ..result });
+        }
+
         let die_results: Vec<DieRollResult> = (0..self.die_count)
-            .map(|_| {
-                let value = self.die.roll();
-                DieRollResult {
-                    die: Die { sides: self.die.sides },
-                    result: value,
-                    keep: true, // Initially mark all as kept
-                }
-            })
+            .map(|_| DieRollResult::new(self.die.clone(), self.die.roll_with(rng)))
             .collect();
         
-        DiceRollResult::create(die_results, self.operation.clone(), self.modifier.clone())
+        let result = DiceRollResult::create(die_results, self.operations.clone(), self.modifier, rng, budget)?;
+        Ok(DiceRollResult { label: self.label.clone(), ..result })
+    }
+}
+
+#[derive(Deserialize)]
+struct DiceRollFields {
+    die: Die,
+    die_count: u32,
+    operations: Vec<DiceRollOp>,
+    modifier: Option<i32>,
+    #[serde(default)]
+    label: Option<String>,
+}
+
+impl TryFrom<DiceRollFields> for DiceRoll {
+    type Error = DiceError;
+
+    fn try_from(fields: DiceRollFields) -> Result<Self, DiceError> {
+        let roll = Self::create(fields.die, fields.die_count, fields.operations, fields.modifier)?;
+        Ok(Self { label: fields.label, ..roll })
     }
 }
 
+// Canonical notation, e.g. "4d6kh3[STR]+2". It always parses back, via
+// from_expression, to a DiceRoll equal to this one.
 impl fmt::Display for DiceRoll {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let op = match &self.operation {
-            Some(DiceRollOp::KeepHighest(n)) => format!("kh{}", n),
-            Some(DiceRollOp::KeepLowest(n)) => format!("kl{}", n),
-            None => String::new(),
-        };
+        // A bare "!" would take a success count's ">=5" as its own threshold,
+        // so the highest face is written out before one, as in "4d6!6>=5"
+        let ops: String = self.operations.iter()
+            .zip(self.operations.iter().skip(1).map(Some).chain([None]))
+            .map(|(op, next)| match (op, next) {
+                (DiceRollOp::Explode { kind, threshold: None }, Some(DiceRollOp::CountSuccesses { .. })) => {
+                    let threshold = Some(Comparison::new(CompareOp::Equal, self.die.max_face()));
+                    DiceRollOp::Explode { kind: *kind, threshold }.to_string()
+                },
+                _ => op.to_string(),
+            })
+            .collect();
         
         let modifier = match self.modifier {
             Some(m) if m > 0 => format!("+{}", m),
@@ -114,6 +241,63 @@ impl fmt::Display for DiceRoll {
             _ => String::new(),
         };
         
-        write!(f, "{}{}{}{}", self.die_count, self.die, op, modifier)
+        // The label goes before the modifier, where it still belongs to the dice
+        let label = match &self.label {
+            Some(label) => escape_label(label),
+            None => String::new(),
+        };
+        
+        write!(f, "{}{}{}{}{}", self.die_count, self.die, ops, label, modifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::ExplodeKind;
+
+    fn assert_round_trip(dice: &DiceRoll) {
+        let text = dice.to_string();
+        assert_eq!(DiceRoll::from_expression(&text).as_ref(), Ok(dice), "{}", text);
+    }
+
+    #[test]
+    fn explosion_before_a_success_count_parses_back() {
+        for kind in [ExplodeKind::Standard, ExplodeKind::Compound, ExplodeKind::Penetrate] {
+            let operations = vec![
+                DiceRollOp::Explode { kind, threshold: None },
+                DiceRollOp::CountSuccesses { success: Comparison::new(CompareOp::GreaterOrEqual, 5), failure: None },
+            ];
+            let dice = DiceRoll::create(Die::new(6).unwrap(), 4, operations, None).unwrap();
+            assert_round_trip(&dice);
+        }
+        assert_eq!(DiceRoll::from_expression("4d6!6>=5").unwrap().to_string(), "4d6!6>=5");
+        assert_eq!(DiceRoll::from_expression("4d6!6").unwrap().to_string(), "4d6!");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn labels_parse_back() {
+        for label in ["atk", "a]b", "back\\slash", "a [b] c"] {
+            let dice = DiceRoll::create(Die::new(20).unwrap(), 1, Vec::new(), Some(5)).unwrap();
+            assert_round_trip(&DiceRoll { label: Some(label.to_string()), ..dice });
+        }
+    }
+
+    #[test]
+    fn modifiers_fold_into_the_roll() {
+        let dice = DiceRoll::from_expression("2d6-3").unwrap();
+        assert_eq!(dice.modifier, Some(-3));
+        assert_round_trip(&dice);
+        assert_eq!(DiceRoll::from_expression("1d20+5 adv").unwrap().to_string(), "2d20kh1+5");
+        assert_eq!(DiceRoll::from_expression("1d20+1d4"), Err(DiceError::NotASingleTerm));
+    }
+
+    #[test]
+    fn advantage_leaves_keeps_alone() {
+        let dice = DiceRoll::from_expression("1d20kl1").unwrap();
+        assert!(!dice.takes_advantage());
+        assert_eq!(dice.with_advantage(Advantage::Advantage), Ok(dice.clone()));
+        let dice = DiceRoll::from_expression("1d20").unwrap();
+        assert_eq!(dice.with_advantage(Advantage::Disadvantage).unwrap().to_string(), "2d20kl1");
+    }
+}
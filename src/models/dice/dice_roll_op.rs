@@ -1,6 +1,174 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::Comparison;
+
+// Longest chain of extra dice a single die may explode into, by default; see DiceLimits
+pub const MAX_EXPLOSION_CHAIN: usize = 100;
+
+// Most times a single die is rerolled before its last value is accepted
+pub const MAX_REROLLS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplodeKind {
+    Standard,   // "!": every explosion adds another die
+    Compound,   // "!!": explosions are added onto the die that triggered them
+    Penetrate,  // "!p": like Standard, but each extra die counts one less
+}
+
 // Use this (preferred)
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DiceRollOp {
     KeepHighest(u32),
     KeepLowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+    // Explodes on the highest face unless a threshold is given
+    Explode { kind: ExplodeKind, threshold: Option<Comparison> },
+    // Rerolls dice matching the condition, either once ("ro") or until they stop matching ("r")
+    Reroll { once: bool, condition: Comparison },
+    // Raises lower dice to this value, e.g. Reliable Talent's "min10"
+    Minimum(i32),
+    // Lowers higher dice to this value
+    Maximum(i32),
+    // Turns the roll into a dice pool: each kept die matching `success` counts +1, each matching `failure` -1
+    CountSuccesses { success: Comparison, failure: Option<Comparison> },
+    // Natural rolls that count as critical hits or fumbles, replacing the
+    // default 20 and 1 on a d20. Doesn't change the total.
+    Critical(Comparison),
+    Fumble(Comparison),
+}
+
+impl DiceRollOp {
+    // Operations are applied phase by phase: rerolls, then explosions, then
+    // clamps, then keeping and dropping, then success counting. Within a phase
+    // they run in the order written.
+    //
+    // Clamps come after explosions so dice explode on what was actually rolled.
+    pub fn phase(&self) -> u8 {
+        match self {
+            DiceRollOp::Reroll { .. } => 0,
+            DiceRollOp::Explode { .. } => 1,
+            DiceRollOp::Minimum(_) | DiceRollOp::Maximum(_) => 2,
+            DiceRollOp::KeepHighest(_) | DiceRollOp::KeepLowest(_)
+            | DiceRollOp::DropHighest(_) | DiceRollOp::DropLowest(_) => 3,
+            DiceRollOp::CountSuccesses { .. } => 4,
+            DiceRollOp::Critical(_) | DiceRollOp::Fumble(_) => 5,
+        }
+    }
+
+    // Human readable description used in roll results, e.g. "keeping highest 3"
+    pub fn describe(&self) -> String {
+        match self {
+            DiceRollOp::KeepHighest(n) => format!("keeping highest {}", n),
+            DiceRollOp::KeepLowest(n) => format!("keeping lowest {}", n),
+            DiceRollOp::DropHighest(n) => format!("dropping highest {}", n),
+            DiceRollOp::DropLowest(n) => format!("dropping lowest {}", n),
+            DiceRollOp::Explode { kind, threshold } => {
+                let name = match kind {
+                    ExplodeKind::Standard => "exploding",
+                    ExplodeKind::Compound => "compounding",
+                    ExplodeKind::Penetrate => "penetrating",
+                };
+                match threshold {
+                    Some(threshold) => format!("{} on {}", name, threshold),
+                    None => name.to_string(),
+                }
+            },
+            DiceRollOp::Minimum(n) => format!("minimum {}", n),
+            DiceRollOp::Maximum(n) => format!("maximum {}", n),
+            DiceRollOp::Reroll { once: true, condition } => format!("rerolling {} once", condition),
+            DiceRollOp::Reroll { once: false, condition } => format!("rerolling {}", condition),
+            DiceRollOp::CountSuccesses { success, failure: Some(failure) } => {
+                format!("successes on {}, failures on {}", success, failure)
+            },
+            DiceRollOp::CountSuccesses { success, failure: None } => format!("successes on {}", success),
+            DiceRollOp::Critical(condition) => format!("critical on {}", condition),
+            DiceRollOp::Fumble(condition) => format!("fumble on {}", condition),
+        }
+    }
+
+    // Indices into values that a keep or drop discards; ties go to the
+    // earlier value, so the result doesn't depend on sort order. Any other
+    // operation discards nothing.
+    pub fn discarded(&self, values: &[i32]) -> Vec<usize> {
+        let mut ranked: Vec<usize> = (0..values.len()).collect();
+        ranked.sort_by_key(|&i| values[i]);
+
+        let count = ranked.len();
+        let discarded = match self {
+            DiceRollOp::KeepHighest(n) => &ranked[..count.saturating_sub(*n as usize)],
+            DiceRollOp::KeepLowest(n) => &ranked[(*n as usize).min(count)..],
+            DiceRollOp::DropHighest(n) => &ranked[count.saturating_sub(*n as usize)..],
+            DiceRollOp::DropLowest(n) => &ranked[..(*n as usize).min(count)],
+            _ => &[],
+        };
+        discarded.to_vec()
+    }
+}
+
+// Expression notation, e.g. "kh3" or "!!>=9"
+impl fmt::Display for DiceRollOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceRollOp::KeepHighest(n) => write!(f, "kh{}", n),
+            DiceRollOp::KeepLowest(n) => write!(f, "kl{}", n),
+            DiceRollOp::DropHighest(n) => write!(f, "dh{}", n),
+            DiceRollOp::DropLowest(n) => write!(f, "dl{}", n),
+            DiceRollOp::Explode { kind, threshold } => {
+                match kind {
+                    ExplodeKind::Standard => write!(f, "!")?,
+                    ExplodeKind::Compound => write!(f, "!!")?,
+                    ExplodeKind::Penetrate => write!(f, "!p")?,
+                }
+                match threshold {
+                    Some(threshold) => write!(f, "{}", threshold),
+                    None => Ok(()),
+                }
+            },
+            DiceRollOp::Minimum(n) => write!(f, "min{}", n),
+            DiceRollOp::Maximum(n) => write!(f, "max{}", n),
+            DiceRollOp::Reroll { once: true, condition } => write!(f, "ro{}", condition),
+            DiceRollOp::Reroll { once: false, condition } => write!(f, "r{}", condition),
+            DiceRollOp::CountSuccesses { success, failure } => {
+                // The operator is always written out, a bare number would run into the die size
+                write!(f, "{}{}", success.op, success.value)?;
+                match failure {
+                    Some(failure) => write!(f, "f{}", failure),
+                    None => Ok(()),
+                }
+            },
+            DiceRollOp::Critical(condition) => write!(f, "cs{}", condition),
+            DiceRollOp::Fumble(condition) => write!(f, "cf{}", condition),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::CompareOp;
+
+    #[test]
+    fn discarding() {
+        let values = [3, 6, 1, 6, 2];
+        assert_eq!(DiceRollOp::KeepHighest(2).discarded(&values), vec![2, 4, 0]);
+        assert_eq!(DiceRollOp::KeepLowest(2).discarded(&values), vec![0, 1, 3]);
+        assert_eq!(DiceRollOp::DropHighest(1).discarded(&values), vec![3]);
+        assert_eq!(DiceRollOp::DropLowest(1).discarded(&values), vec![2]);
+        assert_eq!(DiceRollOp::KeepHighest(9).discarded(&values), Vec::<usize>::new());
+        assert_eq!(DiceRollOp::Minimum(3).discarded(&values), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn notation() {
+        let at_least = |value| Comparison::new(CompareOp::GreaterOrEqual, value);
+        assert_eq!(DiceRollOp::Explode { kind: ExplodeKind::Compound, threshold: Some(at_least(9)) }.to_string(), "!!>=9");
+        assert_eq!(DiceRollOp::Reroll { once: true, condition: Comparison::new(CompareOp::Equal, 1) }.to_string(), "ro1");
+        assert_eq!(
+            DiceRollOp::CountSuccesses { success: Comparison::new(CompareOp::Equal, 6), failure: Some(Comparison::new(CompareOp::Equal, 1)) }.to_string(),
+            "=6f1"
+        );
+    }
 }
@@ -1,72 +1,413 @@
 use std::fmt;
-use super::{DieRollResult, DiceRollOp};
+use serde::{Deserialize, Serialize};
+use super::{CompareOp, Comparison, Die, DieFaces, DieRollResult, DiceRollOp, DiceError, DiceRng, ExplodeKind, RollBudget, TraceNode, MAX_REROLLS};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DiceRollResult {
     pub result: i32, // Changed to i32 to match with modifier
     pub dice_results: Vec<DieRollResult>,
-    pub operation: Option<DiceRollOp>,
+    pub operations: Vec<DiceRollOp>,
     pub modifier: Option<i32>,
+    pub seed: Option<u64>, // Replays this roll with DiceRoll::replay; None from scripted generators
+    #[serde(default)]
+    pub label: Option<String>, // Inline label from the expression, e.g. "atk"
+    #[serde(default)]
+    pub steps: Vec<OperationStep>, // What each operation did, in the order they were applied
+    #[serde(default)]
+    pub summary: Option<RollSummary>, // Set when dice_results only holds the first few dice
+}
+
+// Pools bigger than this are summed as they are rolled when no operation needs
+// to see every die, keeping only this many dice to show
+pub const MAX_SHOWN_DICE: usize = 50;
+
+// Totals over every die of a streamed roll, most of which aren't in dice_results
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RollSummary {
+    pub dice_count: u32,
+    pub sum: i64, // Before the modifier
+    pub lowest: i32,
+    pub highest: i32,
+    pub criticals: u32,
+    pub fumbles: u32,
+}
+
+impl RollSummary {
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / f64::from(self.dice_count.max(1))
+    }
+}
+
+// e.g. "lowest 1, highest 6, average 3.49"
+impl fmt::Display for RollSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lowest {}, highest {}, average {:.2}", self.lowest, self.highest, self.mean())
+    }
+}
+
+// What one operation did to the dice, for the step-by-step trace
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OperationStep {
+    pub operation: DiceRollOp,
+    pub detail: String, // e.g. "discarded 3, 8" or "1→5"
+}
+
+impl OperationStep {
+    // Compares the dice before and after op was applied
+    fn between(op: &DiceRollOp, before: &[DieRollResult], after: &[DieRollResult]) -> Self {
+        let changes: Vec<String> = match op {
+            DiceRollOp::KeepHighest(_) | DiceRollOp::KeepLowest(_)
+            | DiceRollOp::DropHighest(_) | DiceRollOp::DropLowest(_) => {
+                let discarded: Vec<String> = before.iter()
+                    .zip(after)
+                    .filter(|(before, after)| before.keep && !after.keep)
+                    .map(|(_, after)| after.result.to_string())
+                    .collect();
+                if discarded.is_empty() { Vec::new() } else { vec![format!("discarded {}", discarded.join(", "))] }
+            },
+            DiceRollOp::Reroll { .. } => before.iter()
+                .zip(after)
+                .filter(|(before, after)| after.rerolled.len() > before.rerolled.len())
+                .map(|(before, after)| {
+                    let values: Vec<String> = after.rerolled[before.rerolled.len()..].iter()
+                        .chain(std::iter::once(&after.result))
+                        .map(|value| value.to_string())
+                        .collect();
+                    values.join("→")
+                })
+                .collect(),
+            DiceRollOp::Explode { kind: ExplodeKind::Compound, .. } => before.iter()
+                .zip(after)
+                .filter(|(before, after)| after.compounded.len() > before.compounded.len())
+                .map(|(_, after)| after.value_str())
+                .collect(),
+            DiceRollOp::Explode { .. } => {
+                // The original dice keep their order, with the extra dice slotted in after them
+                let mut remaining = before.iter().peekable();
+                let extra: Vec<String> = after.iter()
+                    .filter(|die| remaining.next_if(|old| old == die).is_none())
+                    .map(|die| die.result.to_string())
+                    .collect();
+                if extra.is_empty() { Vec::new() } else { vec![format!("added {}", extra.join(", "))] }
+            },
+            DiceRollOp::Minimum(_) | DiceRollOp::Maximum(_) => before.iter()
+                .zip(after)
+                .filter(|(before, after)| before.result != after.result)
+                .map(|(before, after)| format!("{}→{}", before.result, after.result))
+                .collect(),
+            DiceRollOp::CountSuccesses { .. } => {
+                let successes = after.iter().filter(|die| die.success).count();
+                let failures = after.iter().filter(|die| die.failure).count();
+                vec![format!("{} succeeded, {} failed", successes, failures)]
+            },
+            DiceRollOp::Critical(_) | DiceRollOp::Fumble(_) => Vec::new(),
+        };
+
+        Self {
+            operation: op.clone(),
+            detail: if changes.is_empty() { String::from("no change") } else { changes.join(", ") },
+        }
+    }
 }
 
 impl DiceRollResult {
-    pub fn create(mut dice_results: Vec<DieRollResult>, operation: Option<DiceRollOp>, modifier: Option<i32>) -> Self {
-        // Apply operation (mark dice to keep or discard)
-        if let Some(op) = &operation {
+    // Applies the operations in order; dice keep the order they were rolled in.
+    // The generator is only used by operations that roll extra dice, such as explosions,
+    // and those extra dice are taken out of budget.
+    pub fn create<R: DiceRng + ?Sized>(mut dice_results: Vec<DieRollResult>, operations: Vec<DiceRollOp>, modifier: Option<i32>, rng: &mut R, budget: &mut RollBudget) -> Result<Self, DiceError> {
+        let mut steps = Vec::new();
+        for op in &operations {
+            let before = dice_results.clone();
             match op {
-                DiceRollOp::KeepHighest(n) => {
-                    // Sort dice by result (descending)
-                    dice_results.sort_by(|a, b| b.result.cmp(&a.result));
-                    // Mark dice to keep or discard
-                    for (i, die) in dice_results.iter_mut().enumerate() {
-                        die.keep = i < *n as usize;
+                DiceRollOp::KeepHighest(_) | DiceRollOp::KeepLowest(_)
+                | DiceRollOp::DropHighest(_) | DiceRollOp::DropLowest(_) => {
+                    for index in discarded_by(&dice_results, op) {
+                        dice_results[index].keep = false;
+                    }
+                },
+                DiceRollOp::Explode { kind, threshold } => {
+                    dice_results = explode(dice_results, *kind, *threshold, rng, budget)?;
+                },
+                DiceRollOp::Reroll { once, condition } => {
+                    reroll(&mut dice_results, *once, *condition, rng, budget)?;
+                },
+                DiceRollOp::Minimum(n) | DiceRollOp::Maximum(n) => {
+                    for die in dice_results.iter_mut() {
+                        let clamped = if matches!(op, DiceRollOp::Minimum(_)) {
+                            die.result.max(*n)
+                        } else {
+                            die.result.min(*n)
+                        };
+                        if clamped != die.result {
+                            // Keep the original roll if an earlier clamp already replaced it
+                            die.clamped_from.get_or_insert(die.result);
+                            die.result = clamped;
+                        }
                     }
                 },
-                DiceRollOp::KeepLowest(n) => {
-                    // Sort dice by result (ascending)
-                    dice_results.sort_by(|a, b| a.result.cmp(&b.result));
-                    // Mark dice to keep or discard
-                    for (i, die) in dice_results.iter_mut().enumerate() {
-                        die.keep = i < *n as usize;
+                DiceRollOp::CountSuccesses { success, failure } => {
+                    for die in dice_results.iter_mut().filter(|die| die.keep) {
+                        die.success = success.matches(die.result);
+                        die.failure = failure.is_some_and(|failure| failure.matches(die.result));
                     }
                 },
+                // Marked below, once every die has its final keep flag
+                DiceRollOp::Critical(_) | DiceRollOp::Fumble(_) => continue,
             }
+            steps.push(OperationStep::between(op, &before, &dice_results));
         }
+
+        mark_naturals(&mut dice_results, &operations);
         
-        // Calculate sum of kept dice
-        let sum: i32 = dice_results.iter()
-            .filter(|die| die.keep)
-            .map(|die| die.result as i32)
-            .sum();
+        // Calculate sum of kept dice, or the net successes for a dice pool
+        let sum = if operations.iter().any(|op| matches!(op, DiceRollOp::CountSuccesses { .. })) {
+            let successes = dice_results.iter().filter(|die| die.success).count();
+            let failures = dice_results.iter().filter(|die| die.failure).count();
+            i32::try_from(successes).ok()
+                .zip(i32::try_from(failures).ok())
+                .map(|(successes, failures)| successes - failures)
+                .ok_or(DiceError::Overflow)?
+        } else {
+            dice_results.iter()
+                .filter(|die| die.keep)
+                .try_fold(0i32, |sum, die| sum.checked_add(die.result))
+                .ok_or(DiceError::Overflow)?
+        };
         
         // Apply modifier
         let final_result = if let Some(mod_value) = modifier {
-            sum + mod_value
+            sum.checked_add(mod_value).ok_or(DiceError::Overflow)?
         } else {
             sum
         };
         
-        Self {
+        Ok(Self {
             dice_results,
-            operation,
+            operations,
             modifier,
             result: final_result,
+            seed: None,
+            label: None,
+            steps,
+            summary: None,
+        })
+    }
+
+    // Rolls and sums die_count dice one at a time, without keeping more than
+    // MAX_SHOWN_DICE of them. Only critical and fumble ranges can be applied,
+    // since every other operation needs to see the dice.
+    pub fn stream<R: DiceRng + ?Sized>(die: &Die, die_count: u32, operations: Vec<DiceRollOp>, modifier: Option<i32>, rng: &mut R) -> Result<Self, DiceError> {
+        if operations.iter().any(|op| !matches!(op, DiceRollOp::Critical(_) | DiceRollOp::Fumble(_))) {
+            return Err(DiceError::NotStreamable);
+        }
+        let (critical_on, fumble_on) = natural_ranges(die, &operations);
+
+        let mut dice_results = Vec::with_capacity(MAX_SHOWN_DICE.min(die_count as usize));
+        let mut summary = RollSummary { dice_count: die_count, sum: 0, lowest: i32::MAX, highest: i32::MIN, criticals: 0, fumbles: 0 };
+        for _ in 0..die_count {
+            let value = die.roll_with(rng);
+            let critical = critical_on.is_some_and(|critical| critical.matches(value));
+            let fumble = fumble_on.is_some_and(|fumble| fumble.matches(value));
+
+            // Summed as i64, so only the final total has to fit in an i32
+            summary.sum += i64::from(value);
+            summary.lowest = summary.lowest.min(value);
+            summary.highest = summary.highest.max(value);
+            summary.criticals += u32::from(critical);
+            summary.fumbles += u32::from(fumble);
+
+            if dice_results.len() < MAX_SHOWN_DICE {
+                dice_results.push(DieRollResult { critical, fumble, ..DieRollResult::new(die.clone(), value) });
+            }
         }
+
+        let total = summary.sum + i64::from(modifier.unwrap_or(0));
+        Ok(Self {
+            result: i32::try_from(total).map_err(|_| DiceError::Overflow)?,
+            dice_results,
+            operations,
+            modifier,
+            seed: None,
+            label: None,
+            steps: Vec::new(),
+            summary: Some(summary),
+        })
     }
 }
 
-impl fmt::Display for DiceRollResult {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Format individual dice results
-        let dice_str: Vec<String> = self.dice_results.iter()
-            .map(|die| die.to_string())
+// Rolls the extra dice for every die that triggers an explosion. A chain longer
+// than the budget's limit is an error, so a lucky streak can't run forever.
+fn explode<R: DiceRng + ?Sized>(dice_results: Vec<DieRollResult>, kind: ExplodeKind, threshold: Option<Comparison>, rng: &mut R, budget: &mut RollBudget) -> Result<Vec<DieRollResult>, DiceError> {
+    let mut exploded = Vec::with_capacity(dice_results.len());
+
+    for mut die in dice_results {
+        let triggers = |value: i32| match threshold {
+            Some(threshold) => threshold.matches(value),
+            None => value == die.die.max_face(),
+        };
+
+        let mut chain = Vec::new();
+        let mut last = die.result;
+        while triggers(last) {
+            if chain.len() >= budget.limits.max_explosion_chain {
+                return Err(DiceError::ExplosionChainTooLong { max: budget.limits.max_explosion_chain });
+            }
+            budget.spend(1)?;
+            last = die.die.roll_with(rng);
+            chain.push(last);
+        }
+
+        match kind {
+            ExplodeKind::Compound => {
+                if !chain.is_empty() {
+                    die.compounded = std::iter::once(die.result).chain(chain.iter().copied()).collect();
+                    die.result = die.compounded.iter().fold(0i32, |sum, part| sum.saturating_add(*part));
+                }
+                exploded.push(die);
+            },
+            ExplodeKind::Standard | ExplodeKind::Penetrate => {
+                let extra_dice: Vec<DieRollResult> = chain.into_iter()
+                    .map(|value| {
+                        // Penetrating dice compare the raw roll but count one less
+                        let value = if kind == ExplodeKind::Penetrate { value - 1 } else { value };
+                        DieRollResult { explosion: true, ..DieRollResult::new(die.die.clone(), value) }
+                    })
+                    .collect();
+                exploded.push(die);
+                exploded.extend(extra_dice);
+            },
+        }
+    }
+
+    Ok(exploded)
+}
+
+impl DiceRollResult {
+    // Step by step: the dice as first rolled, what each operation did, then the modifier
+    pub fn trace(&self) -> TraceNode {
+        // Extra dice from explosions show up under the explode step instead
+        let rolled: Vec<_> = self.dice_results.iter().filter(|die| !die.explosion).collect();
+        let count = self.summary.as_ref().map_or(rolled.len(), |summary| summary.dice_count as usize);
+        let notation = match rolled.first() {
+            Some(die) => format!("{}{}", count, die.die),
+            None => String::from("no dice"),
+        };
+
+        // What each die first landed on, before any operation touched it
+        let initial: Vec<String> = rolled.iter()
+            .map(|die| die.rerolled.first().copied().unwrap_or_else(|| die.natural()).to_string())
+            .collect();
+        let mut initial = initial.join(", ");
+        if count > rolled.len() {
+            initial.push_str(&format!(", … {} more", count - rolled.len()));
+        }
+        let mut children = vec![TraceNode::leaf(format!("rolled {}", initial), None)];
+        if let Some(summary) = &self.summary {
+            children.push(TraceNode::leaf(summary.to_string(), None));
+        }
+
+        children.extend(self.steps.iter().map(|step| {
+            TraceNode::leaf(format!("{}: {}", step.operation.describe(), step.detail), None)
+        }));
+
+        match self.modifier {
+            Some(m) if m > 0 => children.push(TraceNode::leaf(format!("+ {}", m), Some(m))),
+            Some(m) if m < 0 => children.push(TraceNode::leaf(format!("- {}", m.abs()), Some(m))),
+            _ => {},
+        }
+        if self.is_critical() {
+            children.push(TraceNode::leaf("natural critical", None));
+        }
+        if self.is_fumble() {
+            children.push(TraceNode::leaf("natural fumble", None));
+        }
+
+        let node = TraceNode::branch(notation, self.result, children);
+        match &self.label {
+            Some(label) => node.labeled(label),
+            None => node,
+        }
+    }
+
+    // Whether this roll counts successes instead of summing the dice
+    pub fn is_dice_pool(&self) -> bool {
+        self.operations.iter().any(|op| matches!(op, DiceRollOp::CountSuccesses { .. }))
+    }
+
+    pub fn successes(&self) -> usize {
+        self.dice_results.iter().filter(|die| die.success).count()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.dice_results.iter().filter(|die| die.failure).count()
+    }
+
+    // Whether any kept die rolled a natural critical, e.g. a natural 20
+    pub fn is_critical(&self) -> bool {
+        match &self.summary {
+            Some(summary) => summary.criticals > 0,
+            None => self.dice_results.iter().any(|die| die.critical),
+        }
+    }
+
+    // Whether any kept die rolled a natural fumble, e.g. a natural 1
+    pub fn is_fumble(&self) -> bool {
+        match &self.summary {
+            Some(summary) => summary.fumbles > 0,
+            None => self.dice_results.iter().any(|die| die.fumble),
+        }
+    }
+
+    // The total in words, e.g. "2 (3 successes, 1 failure)" for dice pools
+    pub fn total_str(&self) -> String {
+        if !self.is_dice_pool() {
+            return self.result.to_string();
+        }
+
+        let count_str = |count: usize, singular: &str, plural: &str| {
+            format!("{} {}", count, if count == 1 { singular } else { plural })
+        };
+        let mut counts = vec![count_str(self.successes(), "success", "successes")];
+        if self.failures() > 0 {
+            counts.push(count_str(self.failures(), "failure", "failures"));
+        }
+        format!("{} ({})", self.result, counts.join(", "))
+    }
+
+    // Everything that goes between "Rolled:" and the total, e.g. "[3], [5] + 2" or "atk: [17]"
+    pub fn breakdown(&self) -> String {
+        // Format individual dice results, with explosion chains shown as one group like [6→6→2]
+        let mut chains: Vec<Vec<&DieRollResult>> = Vec::new();
+        for die in &self.dice_results {
+            match chains.last_mut() {
+                Some(chain) if die.explosion => chain.push(die),
+                _ => chains.push(vec![die]),
+            }
+        }
+        // Past MAX_SHOWN_DICE, discarded dice are only counted, so "10000d6kh1"
+        // shows the one die it kept rather than all the others
+        let discarded = chains.iter().filter(|chain| chain.iter().all(|die| !die.keep)).count();
+        let mut dice_str: Vec<String> = chains.iter()
+            .filter(|chain| discarded <= MAX_SHOWN_DICE || chain.iter().any(|die| die.keep))
+            .map(|chain| format_chain(chain))
             .collect();
+        if discarded > MAX_SHOWN_DICE {
+            dice_str.push(format!("… {} discarded", discarded));
+        }
+        // Streamed rolls only show their first few dice, then what the rest came to
+        if let Some(summary) = &self.summary {
+            let hidden = summary.dice_count as usize - self.dice_results.len();
+            if hidden > 0 {
+                dice_str.push(format!("… {} more ({})", hidden, summary));
+            }
+        }
         
-        // Format operation if present
-        let op_str = match &self.operation {
-            Some(DiceRollOp::KeepHighest(n)) => format!(" (keeping highest {})", n),
-            Some(DiceRollOp::KeepLowest(n)) => format!(" (keeping lowest {})", n),
-            None => String::new(),
+        // Format operations if present
+        let op_str = if self.operations.is_empty() {
+            String::new()
+        } else {
+            let described: Vec<String> = self.operations.iter().map(|op| op.describe()).collect();
+            format!(" ({})", described.join(", "))
         };
         
         // Format modifier if present
@@ -76,11 +417,239 @@ impl fmt::Display for DiceRollResult {
             _ => String::new(),
         };
         
-        write!(f, "Rolled: {}{}{} = {}", 
-            dice_str.join(", "), 
-            op_str,
-            mod_str,
-            self.result
-        )
-    }
-}
\ No newline at end of file
+        let label_str = match &self.label {
+            Some(label) => format!("{}: ", label),
+            None => String::new(),
+        };
+        
+        format!("{}{}{}{}", label_str, dice_str.join(", "), op_str, mod_str)
+    }
+}
+
+// Flags kept dice whose natural roll is a critical or a fumble. Extra dice from
+// explosions don't count, and neither do dice discarded by advantage and the like.
+fn mark_naturals(dice_results: &mut [DieRollResult], operations: &[DiceRollOp]) {
+    for die in dice_results.iter_mut().filter(|die| die.keep && !die.explosion) {
+        let (critical, fumble) = natural_ranges(&die.die, operations);
+        let natural = die.natural();
+        die.critical = critical.is_some_and(|critical| critical.matches(natural));
+        die.fumble = fumble.is_some_and(|fumble| fumble.matches(natural));
+    }
+}
+
+// The natural rolls that count as a critical and a fumble. Set by the last
+// matching operation, otherwise a natural 20 and 1 on a d20 and nothing else.
+fn natural_ranges(die: &Die, operations: &[DiceRollOp]) -> (Option<Comparison>, Option<Comparison>) {
+    let critical_on = operations.iter().rev().find_map(|op| match op {
+        DiceRollOp::Critical(condition) => Some(*condition),
+        _ => None,
+    });
+    let fumble_on = operations.iter().rev().find_map(|op| match op {
+        DiceRollOp::Fumble(condition) => Some(*condition),
+        _ => None,
+    });
+
+    let is_d20 = die.faces == DieFaces::Numbered(20);
+    (
+        critical_on.or(is_d20.then_some(Comparison::new(CompareOp::Equal, 20))),
+        fumble_on.or(is_d20.then_some(Comparison::new(CompareOp::Equal, 1))),
+    )
+}
+
+// Indices of the still-kept dice that a keep or drop operation discards
+fn discarded_by(dice_results: &[DieRollResult], op: &DiceRollOp) -> Vec<usize> {
+    // Rank only the kept dice, without reordering the dice themselves
+    let kept: Vec<usize> = (0..dice_results.len())
+        .filter(|&i| dice_results[i].keep)
+        .collect();
+    let values: Vec<i32> = kept.iter().map(|&i| dice_results[i].result).collect();
+
+    op.discarded(&values).into_iter().map(|rank| kept[rank]).collect()
+}
+
+// Rerolls matching dice in place, remembering the values that were thrown away
+fn reroll<R: DiceRng + ?Sized>(dice_results: &mut [DieRollResult], once: bool, condition: Comparison, rng: &mut R, budget: &mut RollBudget) -> Result<(), DiceError> {
+    let max_rerolls = if once { 1 } else { MAX_REROLLS };
+
+    for die in dice_results.iter_mut() {
+        while condition.matches(die.result) && die.rerolled.len() < max_rerolls {
+            budget.spend(1)?;
+            die.rerolled.push(die.result);
+            die.result = die.die.roll_with(rng);
+        }
+    }
+    Ok(())
+}
+
+fn format_chain(chain: &[&DieRollResult]) -> String {
+    match chain {
+        [die] => die.to_string(),
+        _ => {
+            let values: Vec<String> = chain.iter()
+                .map(|die| if die.keep { die.value_str() } else { format!("({}: discarded)", die.value_str()) })
+                .collect();
+            format!("[{}]", values.join("→"))
+        },
+    }
+}
+
+impl fmt::Display for DiceRollResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rolled: {} = {}", self.breakdown(), self.total_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::dice::{DiceError, DiceRoll, DiceRollResult, FixedSequenceRng};
+
+    // Rolls a single dice term with the given die values, in order
+    fn roll(expression: &str, values: &[u32]) -> Result<DiceRollResult, DiceError> {
+        DiceRoll::from_expression(expression)?.roll_with(&mut FixedSequenceRng::new(values.to_vec()))
+    }
+
+    fn kept(result: &DiceRollResult) -> Vec<i32> {
+        result.dice_results.iter().filter(|die| die.keep).map(|die| die.result).collect()
+    }
+
+    #[test]
+    fn keep_and_drop() {
+        assert_eq!(roll("4d6kh3", &[3, 1, 5, 6]).unwrap().result, 14);
+        assert_eq!(roll("4d6dl1", &[3, 1, 5, 6]).unwrap().result, 14);
+        assert_eq!(roll("4d6kl1", &[3, 1, 5, 6]).unwrap().result, 1);
+        assert_eq!(roll("4d6dh1", &[3, 1, 5, 6]).unwrap().result, 9);
+        assert_eq!(roll("4d6dl1+2", &[3, 1, 5, 6]).unwrap().result, 16);
+    }
+
+    #[test]
+    fn keep_and_drop_chain() {
+        // Drops the 1, then keeps the highest two of what's left
+        let result = roll("5d6dl1kh2", &[4, 1, 6, 2, 5]).unwrap();
+        assert_eq!(kept(&result), vec![6, 5]);
+        assert_eq!(result.result, 11);
+    }
+
+    #[test]
+    fn ties_keep_the_earlier_die() {
+        let result = roll("3d6kh1", &[4, 4, 2]).unwrap();
+        assert!(result.dice_results[1].keep);
+        assert!(!result.dice_results[0].keep);
+        assert_eq!(result.result, 4);
+    }
+
+    #[test]
+    fn keep_and_drop_out_of_range() {
+        assert_eq!(DiceRoll::from_expression("2d6kh3"), Err(DiceError::KeepOutOfRange { keep: 3, die_count: 2 }));
+        assert_eq!(DiceRoll::from_expression("2d6dl2"), Err(DiceError::DropOutOfRange { drop: 2, die_count: 2 }));
+        assert_eq!(DiceRoll::from_expression("4d6dl1kh4"), Err(DiceError::KeepOutOfRange { keep: 4, die_count: 3 }));
+    }
+
+    #[test]
+    fn exploding() {
+        let result = roll("3d6!", &[6, 2, 3, 4]).unwrap();
+        assert_eq!(result.dice_results.len(), 4);
+        assert!(result.dice_results[1].explosion);
+        assert_eq!(result.result, 15);
+
+        assert_eq!(roll("2d10!>=9", &[9, 2, 10, 1]).unwrap().result, 22);
+    }
+
+    #[test]
+    fn compounding_and_penetrating() {
+        let result = roll("1d6!!", &[6, 6, 2]).unwrap();
+        assert_eq!(result.dice_results.len(), 1);
+        assert_eq!(result.result, 14);
+
+        // Each extra die counts one less
+        assert_eq!(roll("1d6!p", &[6, 6, 3]).unwrap().result, 6 + 5 + 2);
+    }
+
+    #[test]
+    fn explosions_stop_at_the_chain_limit() {
+        assert_eq!(roll("1d6!", &[6]), Err(DiceError::ExplosionChainTooLong { max: 100 }));
+        assert_eq!(DiceRoll::from_expression("1d6!>=1"), Err(DiceError::ExplodesOnEveryFace));
+        assert_eq!(DiceRoll::from_expression("1d1!"), Err(DiceError::ExplodesOnEveryFace));
+    }
+
+    #[test]
+    fn rerolling() {
+        // Once, even if the reroll matches again
+        let result = roll("1d6ro1", &[1, 1, 5]).unwrap();
+        assert_eq!(result.result, 1);
+        assert_eq!(result.dice_results[0].rerolled, vec![1]);
+
+        // Until it stops matching
+        assert_eq!(roll("1d6r<=2", &[1, 2, 1, 4]).unwrap().result, 4);
+        assert_eq!(roll("2d6ro<=2", &[1, 5, 3]).unwrap().result, 8);
+    }
+
+    #[test]
+    fn rerolls_stop_at_the_cap() {
+        let result = roll("1d6r1", &[1]).unwrap();
+        assert_eq!(result.dice_results[0].rerolled.len(), crate::models::dice::MAX_REROLLS);
+        assert_eq!(result.result, 1);
+        assert_eq!(DiceRoll::from_expression("1d6r<=6"), Err(DiceError::RerollsOnEveryFace));
+    }
+
+    #[test]
+    fn clamps() {
+        let result = roll("2d20min10", &[3, 15]).unwrap();
+        assert_eq!(result.result, 25);
+        assert_eq!(result.dice_results[0].clamped_from, Some(3));
+        assert_eq!(roll("2d6max4", &[6, 2]).unwrap().result, 6);
+    }
+
+    #[test]
+    fn dice_pools() {
+        let result = roll("5d10>=8f1", &[8, 9, 1, 3, 10]).unwrap();
+        assert!(result.is_dice_pool());
+        assert_eq!((result.successes(), result.failures()), (3, 1));
+        assert_eq!(result.result, 2);
+
+        // Dropped dice don't count
+        assert_eq!(roll("3d10kh2>=8", &[9, 8, 2]).unwrap().result, 2);
+        assert_eq!(roll("3d10kl2>=8", &[9, 8, 2]).unwrap().result, 1);
+    }
+
+    #[test]
+    fn naturals() {
+        assert!(roll("1d20", &[20]).unwrap().is_critical());
+        assert!(roll("1d20", &[1]).unwrap().is_fumble());
+        assert!(!roll("1d20", &[19]).unwrap().is_critical());
+        assert!(roll("1d20cs>=19", &[19]).unwrap().is_critical());
+        // Only the kept d20 counts
+        assert!(!roll("2d20kl1", &[20, 5]).unwrap().is_critical());
+        assert!(roll("2d20kh1", &[20, 5]).unwrap().is_critical());
+    }
+
+    #[test]
+    fn large_pools_are_streamed() {
+        let result = roll("100d6cs6", &[6, 1, 3]).unwrap();
+        let summary = result.summary.clone().unwrap();
+        assert_eq!(summary.dice_count, 100);
+        assert_eq!((summary.lowest, summary.highest), (1, 6));
+        assert_eq!(summary.criticals, 34);
+        assert_eq!(result.dice_results.len(), super::MAX_SHOWN_DICE);
+        assert_eq!(result.result, 34 * 6 + 33 + 33 * 3);
+        assert!(result.is_critical());
+    }
+
+    #[test]
+    fn many_discarded_dice_are_only_counted() {
+        let mut values = vec![1; 59];
+        values.push(6);
+        let result = roll("60d6kh1", &values).unwrap();
+        assert_eq!(result.breakdown(), "[6], … 59 discarded (keeping highest 1)");
+
+        // Up to MAX_SHOWN_DICE of them are still shown one by one
+        assert_eq!(roll("3d6kh1", &[2, 5, 1]).unwrap().breakdown(), "(2: discarded), [5], (1: discarded) (keeping highest 1)");
+    }
+
+    #[test]
+    fn overflowing_totals_are_refused() {
+        assert_eq!(DiceRoll::from_expression("1000d10000000"), Err(DiceError::Overflow));
+        assert_eq!(DiceRoll::from_expression("4294967295d4294967295"), Err(DiceError::Overflow));
+        assert_eq!(DiceRoll::from_expression("0d6"), Err(DiceError::ZeroCount));
+        assert_eq!(DiceRoll::from_expression("1d0"), Err(DiceError::ZeroSides));
+    }
+}
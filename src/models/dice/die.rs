@@ -1,20 +1,173 @@
 use std::fmt;
-use rand::Rng;
+use serde::{Deserialize, Serialize};
+use super::{Comparison, DiceError, DiceRng, ThreadDiceRng};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DieFaces {
+    Numbered(u32),    // 1..=sides, the usual d6 or d20
+    Fudge,            // -1, 0 and +1, as on a Fate die
+    Percentile,       // 1..=100, rolled as a tens die and a ones die
+    Custom(Vec<i32>), // Any listed faces, repeats allowed, e.g. d{2,4,6,8}
+}
+
+// Deserialized through the constructors, so a stored die always has a face to land on
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "DieFields")]
 pub struct Die {
-    pub sides: u32
+    pub faces: DieFaces,
+}
+
+#[derive(Deserialize)]
+struct DieFields {
+    faces: DieFaces,
+}
+
+impl TryFrom<DieFields> for Die {
+    type Error = DiceError;
+
+    fn try_from(fields: DieFields) -> Result<Self, DiceError> {
+        match fields.faces {
+            DieFaces::Numbered(sides) => Self::new(sides),
+            DieFaces::Fudge => Ok(Self::fudge()),
+            DieFaces::Percentile => Ok(Self::percentile()),
+            DieFaces::Custom(faces) => Self::custom(faces),
+        }
+    }
 }
 
 impl Die {
-    pub fn roll(&self) -> u32 {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(1..=self.sides)
+    pub fn new(sides: u32) -> Result<Self, DiceError> {
+        if sides == 0 {
+            return Err(DiceError::ZeroSides);
+        }
+        Ok(Self { faces: DieFaces::Numbered(sides) })
+    }
+
+    pub fn fudge() -> Self {
+        Self { faces: DieFaces::Fudge }
+    }
+
+    pub fn percentile() -> Self {
+        Self { faces: DieFaces::Percentile }
+    }
+
+    pub fn custom(faces: Vec<i32>) -> Result<Self, DiceError> {
+        if faces.is_empty() {
+            return Err(DiceError::ZeroSides);
+        }
+        Ok(Self { faces: DieFaces::Custom(faces) })
+    }
+
+    // Number of faces, counting repeated values separately
+    pub fn sides(&self) -> u32 {
+        match &self.faces {
+            DieFaces::Numbered(sides) => *sides,
+            DieFaces::Fudge => 3,
+            DieFaces::Percentile => 100,
+            DieFaces::Custom(faces) => faces.len() as u32,
+        }
+    }
+
+    // The value on the face at `index`, counting from 1
+    pub fn face(&self, index: u32) -> i32 {
+        match &self.faces {
+            DieFaces::Numbered(_) | DieFaces::Percentile => index as i32,
+            DieFaces::Fudge => index as i32 - 2,
+            DieFaces::Custom(faces) => faces[index as usize - 1],
+        }
+    }
+
+    // Every face value, repeats included
+    pub fn face_values(&self) -> impl Iterator<Item = i32> + '_ {
+        (1..=self.sides()).map(|index| self.face(index))
+    }
+
+    pub fn min_face(&self) -> i32 {
+        match &self.faces {
+            DieFaces::Numbered(_) | DieFaces::Percentile => 1,
+            _ => self.face_values().min().unwrap_or(0),
+        }
+    }
+
+    pub fn max_face(&self) -> i32 {
+        match &self.faces {
+            DieFaces::Numbered(sides) => i32::try_from(*sides).unwrap_or(i32::MAX),
+            DieFaces::Percentile => 100,
+            _ => self.face_values().max().unwrap_or(0),
+        }
+    }
+
+    // True when every face matches, e.g. ">=1" on a numbered die
+    pub fn all_faces_match(&self, condition: &Comparison) -> bool {
+        match &self.faces {
+            DieFaces::Numbered(_) | DieFaces::Percentile => condition.covers_range(self.min_face(), self.max_face()),
+            _ => self.face_values().all(|value| condition.matches(value)),
+        }
+    }
+
+    pub fn roll(&self) -> i32 {
+        self.roll_with(&mut ThreadDiceRng)
+    }
+
+    pub fn roll_with<R: DiceRng + ?Sized>(&self, rng: &mut R) -> i32 {
+        match self.faces {
+            // Tens die first, then ones; "00" and "0" together make 100
+            DieFaces::Percentile => {
+                let tens = rng.roll_range(0, 9) as i32;
+                let ones = rng.roll_range(0, 9) as i32;
+                match tens * 10 + ones {
+                    0 => 100,
+                    value => value,
+                }
+            },
+            _ => self.face(rng.roll_range(1, self.sides())),
+        }
     }
 }
 
-impl fmt::Display for Die {  // This should be Die, not DiceRoll
+// Expression notation: "d6", "dF", "d%" or "d{2,4,6,8}"
+impl fmt::Display for Die {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "d{}", self.sides)
+        match &self.faces {
+            DieFaces::Numbered(sides) => write!(f, "d{}", sides),
+            DieFaces::Fudge => write!(f, "dF"),
+            DieFaces::Percentile => write!(f, "d%"),
+            DieFaces::Custom(faces) => {
+                let faces: Vec<String> = faces.iter().map(|face| face.to_string()).collect();
+                write!(f, "d{{{}}}", faces.join(","))
+            },
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::{CompareOp, FixedSequenceRng};
+
+    #[test]
+    fn faces() {
+        assert_eq!(Die::new(0), Err(DiceError::ZeroSides));
+        assert_eq!(Die::custom(Vec::new()), Err(DiceError::ZeroSides));
+        assert_eq!(Die::fudge().face_values().collect::<Vec<_>>(), vec![-1, 0, 1]);
+        let custom = Die::custom(vec![2, 2, 8]).unwrap();
+        assert_eq!((custom.sides(), custom.min_face(), custom.max_face()), (3, 2, 8));
+        assert!(custom.all_faces_match(&Comparison::new(CompareOp::GreaterOrEqual, 2)));
+        assert!(!Die::new(6).unwrap().all_faces_match(&Comparison::new(CompareOp::Greater, 1)));
+    }
+
+    #[test]
+    fn percentile_dice_roll_tens_then_ones() {
+        let die = Die::percentile();
+        assert_eq!(die.roll_with(&mut FixedSequenceRng::new(vec![4, 2])), 42);
+        assert_eq!(die.roll_with(&mut FixedSequenceRng::new(vec![0, 7])), 7);
+        assert_eq!(die.roll_with(&mut FixedSequenceRng::new(vec![0, 0])), 100);
+    }
+
+    #[test]
+    fn fudge_and_custom_rolls() {
+        assert_eq!(Die::fudge().roll_with(&mut FixedSequenceRng::new(vec![1])), -1);
+        assert_eq!(Die::custom(vec![10, 20, 30]).unwrap().roll_with(&mut FixedSequenceRng::new(vec![3])), 30);
+    }
+}
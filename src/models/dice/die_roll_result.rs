@@ -1,19 +1,90 @@
 use std::fmt;
-use super::Die;
+use serde::{Deserialize, Serialize};
+use super::{Die, DieFaces};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DieRollResult {
     pub die: Die,
-    pub result: u32,
+    pub result: i32,
     pub keep: bool,
+    pub explosion: bool, // Added by the die before it exploding
+    pub compounded: Vec<i32>, // Individual rolls summed into result by a compounding explosion
+    pub rerolled: Vec<i32>, // Earlier values thrown away by rerolls, oldest first
+    pub clamped_from: Option<i32>, // The value rolled before a minimum or maximum replaced it
+    pub success: bool, // Counted as a success in a dice pool
+    pub failure: bool, // Counted as a failure in a dice pool
+    pub critical: bool, // Natural roll in the critical range, e.g. a natural 20
+    pub fumble: bool, // Natural roll in the fumble range, e.g. a natural 1
+}
+
+impl DieRollResult {
+    pub fn new(die: Die, result: i32) -> Self {
+        Self {
+            die,
+            result,
+            keep: true, // Initially mark all as kept
+            explosion: false,
+            compounded: Vec::new(),
+            rerolled: Vec::new(),
+            clamped_from: None,
+            success: false,
+            failure: false,
+            critical: false,
+            fumble: false,
+        }
+    }
+
+    // The face the die first landed on after any rerolls, before compounding or clamping
+    pub fn natural(&self) -> i32 {
+        self.compounded.first().copied()
+            .or(self.clamped_from)
+            .unwrap_or(self.result)
+    }
+
+    // The value on its own, without brackets, e.g. "6", "6→6→2 = 14", "8✔",
+    // "37 (30+7)", "4 (rerolled 1)" or "10 (rolled 4)"
+    pub fn value_str(&self) -> String {
+        let raw = self.clamped_from.unwrap_or(self.result);
+        let mut value = if self.compounded.len() > 1 {
+            let parts: Vec<String> = self.compounded.iter().map(|part| part.to_string()).collect();
+            format!("{} = {}", parts.join("→"), raw)
+        } else if self.die.faces == DieFaces::Percentile {
+            format!("{} ({})", raw, percentile_str(raw))
+        } else {
+            raw.to_string()
+        };
+
+        if self.clamped_from.is_some() {
+            value = format!("{} (rolled {})", self.result, value);
+        }
+
+        if self.success {
+            value.push('✔');
+        }
+        if self.failure {
+            value.push('✘');
+        }
+
+        if self.rerolled.is_empty() {
+            value
+        } else {
+            let rerolled: Vec<String> = self.rerolled.iter().map(|old| old.to_string()).collect();
+            format!("{} (rerolled {})", value, rerolled.join(", "))
+        }
+    }
+}
+
+// The tens and ones dice behind a percentile roll, e.g. "30+7" for 37 or "00+0" for 100
+fn percentile_str(value: i32) -> String {
+    format!("{:02}+{}", value.rem_euclid(100) / 10 * 10, value.rem_euclid(10))
 }
 
 impl fmt::Display for DieRollResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.keep {
-            write!(f, "[{}]", self.result)
+            write!(f, "[{}]", self.value_str())
         } else {
-            write!(f, "({}: discarded)", self.result)
+            write!(f, "({}: discarded)", self.value_str())
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use super::{BinaryOp, DiceError};
+
+// An exact value inside a rounding function, e.g. the 7/2 in "ceil(7/2+1)",
+// kept in lowest terms with the sign on the numerator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fraction {
+    pub numerator: i64,
+    pub denominator: i64,
+}
+
+impl Fraction {
+    pub fn new(numerator: i64, denominator: i64) -> Result<Self, DiceError> {
+        if denominator == 0 {
+            return Err(DiceError::DivisionByZero);
+        }
+        let divisor = gcd(numerator, denominator);
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            let numerator = numerator.checked_neg().ok_or(DiceError::Overflow)?;
+            let denominator = denominator.checked_neg().ok_or(DiceError::Overflow)?;
+            return Ok(Self { numerator, denominator });
+        }
+        Ok(Self { numerator, denominator })
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn negate(&self) -> Result<Self, DiceError> {
+        let numerator = self.numerator.checked_neg().ok_or(DiceError::Overflow)?;
+        Ok(Self { numerator, denominator: self.denominator })
+    }
+
+    // Like BinaryOp::apply, but a division stays exact instead of rounding down
+    pub fn apply(&self, op: BinaryOp, other: &Fraction) -> Result<Self, DiceError> {
+        let (a, b, c, d) = (self.numerator, self.denominator, other.numerator, other.denominator);
+        let (numerator, denominator) = match op {
+            BinaryOp::Add => (checked_cross(a, d, c, b, i64::checked_add)?, b.checked_mul(d)),
+            BinaryOp::Subtract => (checked_cross(a, d, c, b, i64::checked_sub)?, b.checked_mul(d)),
+            BinaryOp::Multiply => (a.checked_mul(c).ok_or(DiceError::Overflow)?, b.checked_mul(d)),
+            BinaryOp::Divide => (a.checked_mul(d).ok_or(DiceError::Overflow)?, b.checked_mul(c)),
+        };
+        Self::new(numerator, denominator.ok_or(DiceError::Overflow)?)
+    }
+}
+
+impl From<i32> for Fraction {
+    fn from(value: i32) -> Self {
+        Self { numerator: i64::from(value), denominator: 1 }
+    }
+}
+
+// As a decimal for the breakdown, e.g. "4.5" or "3.33"
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            return write!(f, "{}", self.numerator);
+        }
+        let decimal = format!("{:.2}", self.numerator as f64 / self.denominator as f64);
+        write!(f, "{}", decimal.trim_end_matches('0'))
+    }
+}
+
+// a*d op c*b, the numerator of a/b op c/d over the denominator b*d
+fn checked_cross(a: i64, d: i64, c: i64, b: i64, op: fn(i64, i64) -> Option<i64>) -> Result<i64, DiceError> {
+    let lhs = a.checked_mul(d).ok_or(DiceError::Overflow)?;
+    let rhs = c.checked_mul(b).ok_or(DiceError::Overflow)?;
+    op(lhs, rhs).ok_or(DiceError::Overflow)
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    // Only zero over zero leaves nothing to divide by, and that's refused above
+    i64::try_from(a.max(1)).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction(numerator: i64, denominator: i64) -> Fraction {
+        Fraction::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn kept_in_lowest_terms() {
+        assert_eq!(fraction(6, -4), Fraction { numerator: -3, denominator: 2 });
+        assert_eq!(fraction(0, 5), Fraction::from(0));
+        assert_eq!(Fraction::new(1, 0), Err(DiceError::DivisionByZero));
+    }
+
+    #[test]
+    fn arithmetic_stays_exact() {
+        let half = fraction(7, 2);
+        assert_eq!(half.apply(BinaryOp::Add, &Fraction::from(0)), Ok(half));
+        assert_eq!(half.apply(BinaryOp::Subtract, &fraction(1, 2)), Ok(Fraction::from(3)));
+        assert_eq!(half.apply(BinaryOp::Multiply, &Fraction::from(2)), Ok(Fraction::from(7)));
+        assert_eq!(half.apply(BinaryOp::Divide, &fraction(-7, 4)), Ok(Fraction::from(-2)));
+        assert_eq!(half.apply(BinaryOp::Divide, &Fraction::from(0)), Err(DiceError::DivisionByZero));
+        assert_eq!(Fraction::from(i32::MAX).apply(BinaryOp::Multiply, &fraction(i64::MAX, 1)), Err(DiceError::Overflow));
+        assert_eq!((half.to_string(), fraction(10, 3).to_string()), (String::from("3.5"), String::from("3.33")));
+    }
+}
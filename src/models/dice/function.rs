@@ -0,0 +1,134 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::{DiceError, Fraction};
+
+// A named function in an expression, e.g. the "floor" in "floor(8d6/2)"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Function {
+    Floor,
+    Ceil,
+    Round, // Halves round away from zero, so round(7/2) is 4
+    Abs,
+    Min,
+    Max,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "floor" => Some(Function::Floor),
+            "ceil" => Some(Function::Ceil),
+            "round" => Some(Function::Round),
+            "abs" => Some(Function::Abs),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Round => "round",
+            Function::Abs => "abs",
+            Function::Min => "min",
+            Function::Max => "max",
+        }
+    }
+
+    // min and max take any number of arguments, everything else exactly one
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Function::Min | Function::Max => count >= 1,
+            _ => count == 1,
+        }
+    }
+
+    // Rounding functions work on the exact value of their argument, whose
+    // divisions would otherwise already have been rounded down
+    pub fn rounding(&self) -> Option<Rounding> {
+        match self {
+            Function::Floor => Some(Rounding::Floor),
+            Function::Ceil => Some(Rounding::Ceil),
+            Function::Round => Some(Rounding::Round),
+            Function::Abs | Function::Min | Function::Max => None,
+        }
+    }
+
+    // The function applied to already rounded values. Rounding functions pass
+    // their argument through unchanged.
+    pub fn apply(&self, values: &[i32]) -> Result<i32, DiceError> {
+        match self {
+            Function::Floor | Function::Ceil | Function::Round => values.first().copied().ok_or(DiceError::EmptyExpression),
+            Function::Abs => values.first()
+                .ok_or(DiceError::EmptyExpression)?
+                .checked_abs()
+                .ok_or(DiceError::Overflow),
+            Function::Min => values.iter().copied().min().ok_or(DiceError::EmptyExpression),
+            Function::Max => values.iter().copied().max().ok_or(DiceError::EmptyExpression),
+        }
+    }
+}
+
+// How floor, ceil and round turn an exact value into a whole number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+}
+
+impl Rounding {
+    pub fn round(&self, value: Fraction) -> Result<i32, DiceError> {
+        let Fraction { numerator, denominator } = value;
+        let rounded = match self {
+            Rounding::Floor => Some(numerator.div_euclid(denominator)),
+            Rounding::Ceil => numerator.checked_neg().map(|n| -n.div_euclid(denominator)),
+            // Halves round away from zero: (2n + d) / 2d, mirrored for negatives
+            Rounding::Round => numerator.unsigned_abs().checked_mul(2)
+                .and_then(|n| n.checked_add(denominator.unsigned_abs()))
+                .and_then(|n| i64::try_from(n / (2 * denominator.unsigned_abs())).ok())
+                .map(|n| if numerator < 0 { -n } else { n }),
+        };
+        rounded.and_then(|value| i32::try_from(value).ok()).ok_or(DiceError::Overflow)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn division_rounding() {
+        for (function, lhs, rhs, expected) in [
+            (Function::Floor, 7, 2, 3), (Function::Ceil, 7, 2, 4), (Function::Round, 7, 2, 4),
+            (Function::Floor, -7, 2, -4), (Function::Ceil, -7, 2, -3), (Function::Round, -7, 2, -4),
+            (Function::Round, 7, -2, -4), (Function::Round, 8, 3, 3), (Function::Round, 6, 4, 2),
+            (Function::Ceil, 6, 3, 2),
+        ] {
+            let rounding = function.rounding().unwrap();
+            assert_eq!(rounding.round(Fraction::new(lhs, rhs).unwrap()), Ok(expected), "{}({}/{})", function, lhs, rhs);
+        }
+        assert_eq!(Function::Abs.rounding(), None);
+        let too_big = Fraction::from(i32::MIN).negate().unwrap();
+        assert_eq!(Rounding::Floor.round(too_big), Err(DiceError::Overflow));
+    }
+
+    #[test]
+    fn applying() {
+        assert_eq!(Function::Abs.apply(&[-5]), Ok(5));
+        assert_eq!(Function::Abs.apply(&[i32::MIN]), Err(DiceError::Overflow));
+        assert_eq!(Function::Min.apply(&[4, -2, 9]), Ok(-2));
+        assert_eq!(Function::Max.apply(&[4, -2, 9]), Ok(9));
+        assert_eq!(Function::Round.apply(&[3]), Ok(3));
+        assert!(Function::Max.accepts(3) && !Function::Floor.accepts(2));
+    }
+}
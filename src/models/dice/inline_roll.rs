@@ -0,0 +1,189 @@
+use super::{DiceError, DiceExpression, DiceRoll, Die};
+
+// A roll found in free text, e.g. the "1d10 + 4" in "Hit: 9 (1d10 + 4) slashing damage"
+#[derive(Clone, Debug, PartialEq)]
+pub struct InlineRoll {
+    pub start: usize, // Byte span of the roll in the scanned text, including any "[[" and "]]"
+    pub end: usize,
+    pub text: String, // The notation as written, without the brackets
+    pub roll: Result<DiceExpression, DiceError>, // Only "[[...]]" rolls are kept when they don't parse
+    pub kind: InlineRollKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InlineRollKind {
+    Bracketed, // Written as "[[...]]"
+    Dice, // Bare dice found in the text, e.g. "2d6+3"
+    AttackBonus, // "+7 to hit", rolled as a d20
+}
+
+// Finds every roll in a piece of text, in order. Looks for "[[...]]" inline
+// rolls, bare dice such as "2d6+3" or "1d10 + 4", and attack bonuses written
+// as "+7 to hit", which roll a d20.
+pub fn scan_inline_rolls(text: &str) -> Vec<InlineRoll> {
+    let bytes = text.as_bytes();
+    let mut rolls = Vec::new();
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let found = if bytes[position..].starts_with(b"[[") {
+            bracketed_roll(text, position)
+        } else if starts_word(bytes, position) {
+            dice_roll(text, position).or_else(|| attack_bonus(text, position))
+        } else {
+            None
+        };
+
+        match found {
+            Some(roll) => {
+                position = roll.end;
+                rolls.push(roll);
+            },
+            None => position += 1,
+        }
+    }
+
+    rolls
+}
+
+// Whether nothing that belongs to a word or number comes right before position
+fn starts_word(bytes: &[u8], position: usize) -> bool {
+    position == 0 || !(bytes[position - 1].is_ascii_alphanumeric() || bytes[position - 1] == b'@')
+}
+
+// Whether nothing that belongs to a word or number comes right after position
+fn ends_word(bytes: &[u8], position: usize) -> bool {
+    bytes.get(position).is_none_or(|b| !b.is_ascii_alphanumeric())
+}
+
+fn skip_while(bytes: &[u8], mut position: usize, predicate: impl Fn(u8) -> bool) -> usize {
+    while bytes.get(position).is_some_and(|b| predicate(*b)) {
+        position += 1;
+    }
+    position
+}
+
+// "[[2d6+1d4+3]]", holding a whole expression, kept even when the contents
+// don't parse so the error can be shown
+fn bracketed_roll(text: &str, start: usize) -> Option<InlineRoll> {
+    let length = text[start + 2..].find("]]")?;
+    let inner = &text[start + 2..start + 2 + length];
+    Some(InlineRoll {
+        start,
+        end: start + length + 4,
+        text: inner.trim().to_string(),
+        roll: DiceExpression::from_expression(inner),
+        kind: InlineRollKind::Bracketed,
+    })
+}
+
+// Bare dice such as "d20", "4d6kh3" or "1d10 + 4". The longest run that looks
+// like dice is tried first, then shortened until it parses.
+fn dice_roll(text: &str, start: usize) -> Option<InlineRoll> {
+    let bytes = text.as_bytes();
+
+    let die = skip_while(bytes, start, |b| b.is_ascii_digit());
+    if !matches!(bytes.get(die), Some(b'd' | b'D')) {
+        return None;
+    }
+    let sides = skip_while(bytes, die + 1, |b| b.is_ascii_digit() || b == b'%');
+    if sides == die + 1 {
+        return None;
+    }
+
+    // Operations can't contain spaces, e.g. the "kh1" in "2d20kh1"
+    let operations = skip_while(bytes, sides, |b| b.is_ascii_alphanumeric() || b"!<>=".contains(&b));
+
+    // A modifier may have spaces around its sign, but not another dice term after it
+    let mut ends = Vec::new();
+    let sign = skip_while(bytes, operations, |b| b == b' ');
+    if matches!(bytes.get(sign), Some(b'+' | b'-')) {
+        let number = skip_while(bytes, sign + 1, |b| b == b' ');
+        let modifier = skip_while(bytes, number, |b| b.is_ascii_digit());
+        if modifier > number && ends_word(bytes, modifier) {
+            ends.push(modifier);
+        }
+    }
+    ends.extend((sides..=operations).rev());
+
+    ends.into_iter()
+        .filter(|end| ends_word(bytes, *end))
+        .find_map(|end| {
+            let notation = &text[start..end];
+            DiceRoll::from_expression(notation).ok().map(|roll| InlineRoll {
+                start,
+                end,
+                text: notation.to_string(),
+                roll: Ok(DiceExpression::from_roll(roll)),
+                kind: InlineRollKind::Dice,
+            })
+        })
+}
+
+// "+7 to hit", which is a d20 roll with that bonus
+fn attack_bonus(text: &str, start: usize) -> Option<InlineRoll> {
+    let bytes = text.as_bytes();
+
+    let sign = match bytes.get(start) {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return None,
+    };
+    let digits = skip_while(bytes, start + 1, |b| b.is_ascii_digit());
+    let bonus: i32 = text[start + 1..digits].parse().ok()?;
+
+    let to_hit = skip_while(bytes, digits, |b| b == b' ');
+    let rest = text.get(to_hit..to_hit + 6)?;
+    if to_hit == digits || !rest.eq_ignore_ascii_case("to hit") || !ends_word(bytes, to_hit + 6) {
+        return None;
+    }
+
+    let roll = DiceRoll::create(Die::new(20).ok()?, 1, Vec::new(), Some(sign * bonus)).ok()?;
+    Some(InlineRoll {
+        start,
+        end: to_hit + 6,
+        text: text[start..to_hit + 6].to_string(),
+        roll: Ok(DiceExpression::from_roll(roll)),
+        kind: InlineRollKind::AttackBonus,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(text: &str) -> Vec<(&str, InlineRollKind, bool)> {
+        scan_inline_rolls(text).iter()
+            .map(|roll| (&text[roll.start..roll.end], roll.kind, roll.roll.is_ok()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_rolls_in_text() {
+        assert_eq!(found("Hit: 9 (1d10 + 4) slashing damage."), vec![("1d10 + 4", InlineRollKind::Dice, true)]);
+        assert_eq!(found("Longsword. +7 to hit, reach 5 ft."), vec![("+7 to hit", InlineRollKind::AttackBonus, true)]);
+        assert_eq!(found("Roll [[2d20kh1+5]] then d6"), vec![
+            ("[[2d20kh1+5]]", InlineRollKind::Bracketed, true),
+            ("d6", InlineRollKind::Dice, true),
+        ]);
+        assert_eq!(found("[[nonsense]]"), vec![("[[nonsense]]", InlineRollKind::Bracketed, false)]);
+    }
+
+    #[test]
+    fn brackets_hold_whole_expressions() {
+        assert_eq!(found("[[1d20+1d4]] and [[2d6+1d4+3]]"), vec![
+            ("[[1d20+1d4]]", InlineRollKind::Bracketed, true),
+            ("[[2d6+1d4+3]]", InlineRollKind::Bracketed, true),
+        ]);
+        let rolls = scan_inline_rolls("[[ 1d20+5 vs DC 15 ]]");
+        let expression = rolls[0].roll.as_ref().unwrap();
+        assert_eq!((rolls[0].text.as_str(), expression.target.is_some()), ("1d20+5 vs DC 15", true));
+    }
+
+    #[test]
+    fn ignores_words_that_only_look_like_dice() {
+        assert!(found("add6 and 3dx, 2d6y").is_empty());
+        assert_eq!(found("2d6 + 1d4"), vec![("2d6", InlineRollKind::Dice, true), ("1d4", InlineRollKind::Dice, true)]);
+        assert_eq!(found("é 1d4 ü"), vec![("1d4", InlineRollKind::Dice, true)]);
+    }
+}
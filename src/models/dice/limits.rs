@@ -0,0 +1,139 @@
+use std::fmt::{self, Write};
+use serde::{Deserialize, Serialize};
+use super::{DiceError, MAX_EXPLOSION_CHAIN, MAX_REPEAT};
+
+// Caps on how much work a single expression may ask for, from parsing it to
+// showing the result. Every check reports its own DiceError.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiceLimits {
+    pub max_expression_length: usize, // In bytes, checked before tokenizing
+    pub max_depth: usize, // Nested parentheses, groups, function calls and signs
+    pub max_repeat: u32, // The N in "Nx"
+    pub max_dice: u64, // Every die rolled, counting repeats, rerolls and explosions
+    pub max_explosion_chain: usize, // Extra rolls a single die may explode into
+    pub max_output_length: usize, // Characters in the text of the rolled result
+}
+
+impl DiceLimits {
+    // Rolls made in the browser, where only the user's own tab pays for them
+    pub fn client() -> Self {
+        Self {
+            max_expression_length: 1_000,
+            max_depth: 64,
+            max_repeat: MAX_REPEAT,
+            max_dice: 1_000_000,
+            max_explosion_chain: MAX_EXPLOSION_CHAIN,
+            max_output_length: 100_000,
+        }
+    }
+
+    // Rolls made on the server for someone else's request
+    pub fn server() -> Self {
+        Self {
+            max_expression_length: 200,
+            max_depth: 16,
+            max_repeat: 20,
+            max_dice: 10_000,
+            max_explosion_chain: 20,
+            max_output_length: 10_000,
+        }
+    }
+
+    pub fn check_expression_length(&self, expression: &str) -> Result<(), DiceError> {
+        if expression.len() > self.max_expression_length {
+            return Err(DiceError::ExpressionTooLong { length: expression.len(), max: self.max_expression_length });
+        }
+        Ok(())
+    }
+
+    // Formats the values only as far as the limit, so an oversized result is
+    // never built in full just to be thrown away
+    pub fn check_output_length<T: fmt::Display>(&self, values: &[T]) -> Result<(), DiceError> {
+        let mut counter = LengthCounter { length: 0, max: self.max_output_length };
+        for value in values {
+            if write!(counter, "{}", value).is_err() {
+                return Err(DiceError::OutputTooLarge { max: self.max_output_length });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DiceLimits {
+    fn default() -> Self {
+        Self::client()
+    }
+}
+
+// Counts characters written to it, failing once there are more than max
+struct LengthCounter {
+    length: usize,
+    max: usize,
+}
+
+impl Write for LengthCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.length += s.chars().count();
+        if self.length > self.max { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+// The dice still available to one roll of an expression, shared by all of its
+// terms and repetitions
+#[derive(Clone, Debug)]
+pub struct RollBudget {
+    pub limits: DiceLimits,
+    pub dice_rolled: u64,
+}
+
+impl RollBudget {
+    pub fn new(limits: DiceLimits) -> Self {
+        Self { limits, dice_rolled: 0 }
+    }
+
+    // Called before rolling, so a roll over the limit fails without rolling anything
+    pub fn spend(&mut self, dice: u64) -> Result<(), DiceError> {
+        self.dice_rolled = self.dice_rolled.saturating_add(dice);
+        if self.dice_rolled > self.limits.max_dice {
+            return Err(DiceError::TooManyDice { max: self.limits.max_dice });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::{roll_dice_with_limits, DiceExpression, FixedSequenceRng, Variables};
+
+    #[test]
+    fn dice_budget() {
+        let mut budget = RollBudget::new(DiceLimits::server());
+        assert_eq!(budget.spend(10_000), Ok(()));
+        assert_eq!(budget.spend(1), Err(DiceError::TooManyDice { max: 10_000 }));
+    }
+
+    #[test]
+    fn server_limits() {
+        let limits = DiceLimits::server();
+        let mut rng = FixedSequenceRng::new(vec![1]);
+        assert!(roll_dice_with_limits("10000d6", &limits, &mut rng).is_ok());
+        assert_eq!(roll_dice_with_limits("10001d6", &limits, &mut rng), Err(DiceError::TooManyDice { max: 10_000 }));
+        assert_eq!(roll_dice_with_limits("5000d6+5001d6", &limits, &mut rng), Err(DiceError::TooManyDice { max: 10_000 }));
+        // Only the kept die is written out, so the result stays short
+        assert!(roll_dice_with_limits("10000d6kh1", &limits, &mut rng).is_ok());
+        let mut sixes = FixedSequenceRng::new(vec![6]);
+        assert_eq!(roll_dice_with_limits("1d6!", &limits, &mut sixes), Err(DiceError::ExplosionChainTooLong { max: 20 }));
+    }
+
+    #[test]
+    fn output_length() {
+        let limits = DiceLimits { max_output_length: 20, ..DiceLimits::client() };
+        assert_eq!(limits.check_output_length(&["0123456789", "0123456789"]), Ok(()));
+        assert_eq!(limits.check_output_length(&["0123456789", "0123456789", "!"]), Err(DiceError::OutputTooLarge { max: 20 }));
+
+        let expression = DiceExpression::from_expression_with_limits("20d6", &Variables::default(), &limits).unwrap();
+        let result = expression.roll_with_limits(&mut FixedSequenceRng::new(vec![1]), &limits);
+        assert_eq!(result, Err(DiceError::OutputTooLarge { max: 20 }));
+    }
+}
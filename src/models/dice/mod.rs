@@ -1,23 +1,67 @@
 // Re-export all the public items from each module
+pub mod dice_error;
+pub mod dice_rng;
+pub mod comparison;
 pub mod die;
 pub mod die_roll_result;
 pub mod dice_roll_op;
 pub mod dice_roll;
 pub mod dice_roll_result;
+pub mod dice_expression;
+pub mod dice_expression_result;
+pub mod variables;
+pub mod target;
+pub mod function;
+pub mod fraction;
+pub mod advantage;
+pub mod trace;
+pub mod inline_roll;
+pub mod limits;
+pub mod tokenizer;
+pub mod parser;
 
 // Make the important structs and enums directly accessible from the dice module
-pub use die::Die;
+pub use dice_error::DiceError;
+pub use dice_rng::{DiceRng, ThreadDiceRng, SeededDiceRng, FixedSequenceRng};
+pub use comparison::{CompareOp, Comparison};
+pub use die::{Die, DieFaces};
 pub use die_roll_result::DieRollResult;
-pub use dice_roll_op::DiceRollOp;
+pub use dice_roll_op::{DiceRollOp, ExplodeKind, MAX_EXPLOSION_CHAIN, MAX_REROLLS};
 pub use dice_roll::DiceRoll;
-pub use dice_roll_result::DiceRollResult;
+pub use dice_roll_result::{DiceRollResult, OperationStep, RollSummary, MAX_SHOWN_DICE};
+pub use dice_expression::{BinaryOp, DiceExpression, ExpressionNode, MAX_REPEAT};
+pub use dice_expression_result::{DiceExpressionResult, ExpressionNodeResult};
+pub use variables::Variables;
+pub use target::{Target, TargetKind, TargetOutcome};
+pub use function::{Function, Rounding};
+pub use fraction::Fraction;
+pub use advantage::Advantage;
+pub use trace::TraceNode;
+pub use inline_roll::{InlineRoll, InlineRollKind, scan_inline_rolls};
+pub use limits::{DiceLimits, RollBudget};
 
 // Optionally, provide convenience functions at the module level
-pub fn parse_dice_expression(expression: &str) -> Result<DiceRoll, String> {
-    DiceRoll::from_expression(expression)
+pub fn parse_dice_expression(expression: &str) -> Result<DiceExpression, DiceError> {
+    DiceExpression::from_expression(expression)
 }
 
-pub fn roll_dice(expression: &str) -> Result<DiceRollResult, String> {
-    let dice_roll = parse_dice_expression(expression)?;
-    Ok(dice_roll.roll())
-}
\ No newline at end of file
+// Like parse_dice_expression, resolving "@name" references against variables
+pub fn parse_dice_expression_with_variables(expression: &str, variables: &Variables) -> Result<DiceExpression, DiceError> {
+    DiceExpression::from_expression_with_variables(expression, variables)
+}
+
+pub fn roll_dice(expression: &str) -> Result<DiceExpressionResult, DiceError> {
+    roll_dice_with(expression, &mut ThreadDiceRng)
+}
+
+pub fn roll_dice_with<R: DiceRng + ?Sized>(expression: &str, rng: &mut R) -> Result<DiceExpressionResult, DiceError> {
+    let expression = parse_dice_expression(expression)?;
+    expression.roll_with(rng)
+}
+
+// Parses and rolls within explicit limits, e.g. DiceLimits::server() for rolls
+// made on someone else's behalf
+pub fn roll_dice_with_limits<R: DiceRng + ?Sized>(expression: &str, limits: &DiceLimits, rng: &mut R) -> Result<DiceExpressionResult, DiceError> {
+    let expression = DiceExpression::from_expression_with_limits(expression, &Variables::default(), limits)?;
+    expression.roll_with_limits(rng, limits)
+}
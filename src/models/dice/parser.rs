@@ -0,0 +1,726 @@
+// Recursive descent parser for dice expressions.
+//
+// Grammar, lowest precedence first:
+//   input      := (NUMBER "x")? expression advantage* target? DESCRIPTION?
+//   advantage  := "adv" | "advantage" | "dis" | "disadvantage" | "ea"
+//   target     := ("vs" compare_op? | compare_op) ("ac" | "dc")? signed
+//   expression := product (("+" | "-") product)*
+//   product    := unary (("*" | "/") unary)*
+//   unary      := ("+" | "-") unary | labeled
+//   labeled    := primary LABEL?
+//   primary    := dice | NUMBER | VARIABLE | group | function | "(" expression ")"
+//   function   := ("floor" | "ceil" | "round" | "abs" | "min" | "max") "(" expression ("," expression)* ")"
+//   group      := "{" expression ("," expression)* "}" (("kh" | "kl" | "dh" | "dl") NUMBER?)?
+//   dice       := NUMBER? "d" faces operation*
+//   faces      := NUMBER | "f" | "%" | "{" signed ("," signed)* "}"
+//   operation  := ("kh" | "kl" | "dh" | "dl") NUMBER? | ("min" | "max") signed
+//               | ("cs" | "cf") comparison | explode | reroll | successes
+//   explode    := ("!" | "!!" | "!p") comparison?
+//   reroll     := ("r" | "ro") comparison
+//   successes  := compare_op signed ("f" comparison)?
+//   comparison := compare_op signed | NUMBER
+//   signed     := "-"? NUMBER
+//   compare_op := "=" | "<" | "<=" | ">" | ">="
+//
+// Letters are tokenized as whole words, so operation keywords are matched as
+// prefixes: "4d6!pkh3" arrives as the word "pkh" and is split into "p" and "kh",
+// and "4dF" arrives as "df" and is split into "d" and "f". A "d" only starts
+// dice when faces follow it, so the "dis" of "1d20+5 dis" stays a word. LABEL
+// is "[text]" and DESCRIPTION is everything after a "#".
+//
+// A comparison straight after several dice counts successes, so "2d6>=5" is a
+// dice pool. Anywhere else, including after a single die, it is a target for
+// the whole roll: "1d20>=15" passes or fails just like "1d20+5>=15". "vs" and
+// an explicit "ac" or "dc" always mean a target: "1d20 vs 15", "1d20 vs <12"
+// and "1d20>=AC 15". Advantage needs exactly one single d20 without a keep or
+// drop to apply to, and advantage and disadvantage together cancel out.
+// VARIABLE is "@name", looked up while parsing so an unknown name is reported
+// like any other syntax error.
+//
+// The input's length, how deeply it nests and how many times it repeats are
+// checked against DiceLimits as it is parsed.
+use super::tokenizer::{tokenize, Token, TokenKind};
+use super::{Advantage, BinaryOp, CompareOp, Function, Comparison, DiceError, DiceLimits, DiceExpression, Die, DiceRoll, DiceRollOp, ExplodeKind, ExpressionNode, Target, TargetKind, Variables};
+
+pub fn parse(input: &str) -> Result<DiceExpression, DiceError> {
+    parse_with_variables(input, &Variables::default())
+}
+
+pub fn parse_with_variables(input: &str, variables: &Variables) -> Result<DiceExpression, DiceError> {
+    parse_with_limits(input, variables, &DiceLimits::default())
+}
+
+pub fn parse_with_limits(input: &str, variables: &Variables, limits: &DiceLimits) -> Result<DiceExpression, DiceError> {
+    limits.check_expression_length(input)?;
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0, variables, limits, depth: 0 };
+
+    let repeat = parser.parse_repeat()?;
+
+    if matches!(parser.peek_kind(), None | Some(TokenKind::Description(_))) {
+        return Err(DiceError::EmptyExpression);
+    }
+
+    let root = parser.parse_expression()?;
+    let advantage = parser.parse_advantage(&root)?;
+    let target = parser.parse_target()?;
+
+    let description = match parser.peek_kind() {
+        Some(TokenKind::Description(description)) => {
+            let description = description.clone();
+            parser.next();
+            Some(description).filter(|description| !description.is_empty())
+        },
+        _ => None,
+    };
+
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+
+    Ok(DiceExpression { root, description, target, repeat, advantage })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a Variables,
+    limits: &'a DiceLimits,
+    depth: usize, // How many expressions and signs the current token is nested in
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_kind(&self) -> Option<&TokenKind> {
+        self.peek().map(|token| &token.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self) -> DiceError {
+        match self.peek() {
+            Some(token) => DiceError::UnexpectedToken {
+                token: token.kind.to_string(),
+                position: token.position,
+            },
+            None => DiceError::UnexpectedEnd,
+        }
+    }
+
+    // A leading "6x" rolls the whole expression six times
+    fn parse_repeat(&mut self) -> Result<Option<u32>, DiceError> {
+        let is_repeat = matches!(
+            (self.peek_kind(), self.tokens.get(self.pos + 1)),
+            (Some(TokenKind::Number(_)), Some(Token { kind: TokenKind::Word(word), .. })) if word.starts_with('x')
+        );
+        if !is_repeat {
+            return Ok(None);
+        }
+
+        let repeat = self.take_number().unwrap_or(1);
+        self.take_keyword("x");
+        if repeat == 0 || repeat > self.limits.max_repeat {
+            return Err(DiceError::RepeatOutOfRange { repeat, max: self.limits.max_repeat });
+        }
+        Ok(Some(repeat))
+    }
+
+    // Parses with the nesting depth raised by one, so a deeply nested input
+    // fails instead of overflowing the stack
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, DiceError>) -> Result<T, DiceError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(DiceError::NestedTooDeeply { max: self.limits.max_depth });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_expression(&mut self) -> Result<ExpressionNode, DiceError> {
+        self.nested(Self::parse_sum)
+    }
+
+    fn parse_sum(&mut self) -> Result<ExpressionNode, DiceError> {
+        let mut lhs = self.parse_product()?;
+
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Subtract,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_product()?;
+            lhs = ExpressionNode::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) };
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_product(&mut self) -> Result<ExpressionNode, DiceError> {
+        let mut lhs = self.parse_unary()?;
+
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Star) => BinaryOp::Multiply,
+                Some(TokenKind::Slash) => BinaryOp::Divide,
+                _ => break,
+            };
+            self.next();
+            let rhs = self.parse_unary()?;
+            lhs = ExpressionNode::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) };
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<ExpressionNode, DiceError> {
+        match self.peek_kind() {
+            Some(TokenKind::Minus) => {
+                self.next();
+                Ok(ExpressionNode::Negate(Box::new(self.nested(Self::parse_unary)?)))
+            },
+            Some(TokenKind::Plus) => {
+                self.next();
+                self.nested(Self::parse_unary)
+            },
+            _ => self.parse_labeled(),
+        }
+    }
+
+    // A term with an optional "[label]" after it. Dice keep the label themselves.
+    fn parse_labeled(&mut self) -> Result<ExpressionNode, DiceError> {
+        let node = self.parse_primary()?;
+
+        let Some(TokenKind::Label(label)) = self.peek_kind() else {
+            return Ok(node);
+        };
+        let label = label.clone();
+        self.next();
+
+        Ok(match node {
+            ExpressionNode::Roll(dice) if dice.label.is_none() => {
+                ExpressionNode::Roll(DiceRoll { label: Some(label), ..dice })
+            },
+            node => ExpressionNode::Labeled { inner: Box::new(node), label },
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<ExpressionNode, DiceError> {
+        // "d20" is shorthand for "1d20"
+        if self.take_dice() {
+            return self.parse_dice(1);
+        }
+
+        // A function name only counts as one when it's called, e.g. "max(" but not "max"
+        if let Some(function) = self.peek_function() {
+            self.pos += 2;
+            return self.parse_function(function);
+        }
+
+        match self.peek_kind() {
+            Some(TokenKind::Number(n)) => {
+                let n = *n;
+                let position = self.next().map_or(0, |token| token.position);
+                if self.take_dice() {
+                    self.parse_dice(n)
+                } else {
+                    let value = i32::try_from(n).map_err(|_| DiceError::NumberTooLarge { position })?;
+                    Ok(ExpressionNode::Constant(value))
+                }
+            },
+            Some(TokenKind::Variable(name)) => {
+                let name = name.clone();
+                let position = self.next().map_or(0, |token| token.position);
+                match self.variables.get(&name) {
+                    Some(value) => Ok(ExpressionNode::Variable { name, value }),
+                    None => Err(DiceError::UnknownVariable { name, position }),
+                }
+            },
+            Some(TokenKind::LeftBrace) => {
+                self.next();
+                self.parse_group()
+            },
+            Some(TokenKind::LeftParen) => {
+                self.next();
+                let inner = self.parse_expression()?;
+                match self.peek_kind() {
+                    Some(TokenKind::RightParen) => {
+                        self.next();
+                        Ok(inner)
+                    },
+                    _ => Err(self.unexpected()),
+                }
+            },
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn peek_function(&self) -> Option<Function> {
+        let Some(TokenKind::Word(name)) = self.peek_kind() else {
+            return None;
+        };
+        let called = matches!(self.tokens.get(self.pos + 1), Some(Token { kind: TokenKind::LeftParen, .. }));
+        Function::from_name(name).filter(|_| called)
+    }
+
+    // Called with the name and "(" already consumed
+    fn parse_function(&mut self, function: Function) -> Result<ExpressionNode, DiceError> {
+        let mut args = vec![self.parse_expression()?];
+        loop {
+            match self.peek_kind() {
+                Some(TokenKind::Comma) => {
+                    self.next();
+                    args.push(self.parse_expression()?);
+                },
+                Some(TokenKind::RightParen) => {
+                    self.next();
+                    break;
+                },
+                _ => return Err(self.unexpected()),
+            }
+        }
+
+        ExpressionNode::function(function, args)
+    }
+
+    // Called with the "{" already consumed
+    fn parse_group(&mut self) -> Result<ExpressionNode, DiceError> {
+        let mut items = vec![self.parse_expression()?];
+        loop {
+            match self.peek_kind() {
+                Some(TokenKind::Comma) => {
+                    self.next();
+                    items.push(self.parse_expression()?);
+                },
+                Some(TokenKind::RightBrace) => {
+                    self.next();
+                    break;
+                },
+                _ => return Err(self.unexpected()),
+            }
+        }
+
+        // Explosions and the like only make sense on dice, so only a keep or drop can follow
+        let keep = self.parse_keep();
+        ExpressionNode::group(items, keep)
+    }
+
+    // Called with the "d" already consumed
+    fn parse_dice(&mut self, die_count: u32) -> Result<ExpressionNode, DiceError> {
+        let die = self.parse_faces()?;
+
+        let mut operations = Vec::new();
+        while let Some(op) = self.parse_operation(die_count)? {
+            operations.push(op);
+        }
+
+        Ok(ExpressionNode::Roll(DiceRoll::create(die, die_count, operations, None)?))
+    }
+
+    fn parse_faces(&mut self) -> Result<Die, DiceError> {
+        if let Some(sides) = self.take_number() {
+            return Die::new(sides);
+        }
+        if self.take_keyword("f") {
+            return Ok(Die::fudge());
+        }
+        if self.peek_kind() == Some(&TokenKind::Percent) {
+            self.next();
+            return Ok(Die::percentile());
+        }
+        if self.peek_kind() != Some(&TokenKind::LeftBrace) {
+            return Err(self.unexpected());
+        }
+        self.next();
+
+        let mut faces = Vec::new();
+        loop {
+            match self.take_signed_number()? {
+                Some(face) => faces.push(face),
+                None => return Err(self.unexpected()),
+            }
+            match self.peek_kind() {
+                Some(TokenKind::Comma) => {
+                    self.next();
+                },
+                Some(TokenKind::RightBrace) => {
+                    self.next();
+                    return Die::custom(faces);
+                },
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    // A bare "kh", "kl", "dh" or "dl" keeps or drops a single die or group item
+    fn parse_keep(&mut self) -> Option<DiceRollOp> {
+        for keyword in ["kh", "kl", "dh", "dl"] {
+            if self.take_keyword(keyword) {
+                let n = self.take_number().unwrap_or(1);
+                return Some(match keyword {
+                    "kh" => DiceRollOp::KeepHighest(n),
+                    "kl" => DiceRollOp::KeepLowest(n),
+                    "dh" => DiceRollOp::DropHighest(n),
+                    _ => DiceRollOp::DropLowest(n),
+                });
+            }
+        }
+        None
+    }
+
+    fn parse_operation(&mut self, die_count: u32) -> Result<Option<DiceRollOp>, DiceError> {
+        if let Some(keep) = self.parse_keep() {
+            return Ok(Some(keep));
+        }
+
+        for keyword in ["min", "max"] {
+            if self.take_keyword(keyword) {
+                let Some(n) = self.take_signed_number()? else {
+                    return Err(self.unexpected());
+                };
+                return Ok(Some(if keyword == "min" { DiceRollOp::Minimum(n) } else { DiceRollOp::Maximum(n) }));
+            }
+        }
+
+        for keyword in ["cs", "cf"] {
+            if self.take_keyword(keyword) {
+                let Some(condition) = self.parse_comparison()? else {
+                    return Err(self.unexpected());
+                };
+                return Ok(Some(if keyword == "cs" { DiceRollOp::Critical(condition) } else { DiceRollOp::Fumble(condition) }));
+            }
+        }
+
+        // "ro" has to be tried before "r", which is also its prefix
+        for (keyword, once) in [("ro", true), ("r", false)] {
+            if self.take_keyword(keyword) {
+                return match self.parse_comparison()? {
+                    Some(condition) => Ok(Some(DiceRollOp::Reroll { once, condition })),
+                    None => Err(self.unexpected()),
+                };
+            }
+        }
+
+        if self.peek_kind() == Some(&TokenKind::Bang) {
+            self.next();
+            let kind = if self.peek_kind() == Some(&TokenKind::Bang) {
+                self.next();
+                ExplodeKind::Compound
+            } else if self.take_keyword("p") {
+                ExplodeKind::Penetrate
+            } else {
+                ExplodeKind::Standard
+            };
+            let threshold = self.parse_comparison()?;
+            return Ok(Some(DiceRollOp::Explode { kind, threshold }));
+        }
+
+        // A dice pool target needs an explicit operator, e.g. ">=7" rather than "7".
+        // A single die is no pool, so its comparison is left for the target.
+        if die_count > 1 && self.peek_compare_op().is_some() && !self.peek_target_kind() {
+            let Some(success) = self.parse_comparison()? else {
+                return Err(self.unexpected());
+            };
+            let failure = if self.take_keyword("f") {
+                match self.parse_comparison()? {
+                    Some(failure) => Some(failure),
+                    None => return Err(self.unexpected()),
+                }
+            } else {
+                None
+            };
+            return Ok(Some(DiceRollOp::CountSuccesses { success, failure }));
+        }
+
+        Ok(None)
+    }
+
+    // Any number of "adv", "dis" and "ea" after the expression, resolved into one
+    fn parse_advantage(&mut self, root: &ExpressionNode) -> Result<Option<Advantage>, DiceError> {
+        let mut all = Vec::new();
+        while let Some(advantage) = Advantage::KEYWORDS.iter()
+            .find(|(keyword, _)| self.take_keyword(keyword))
+            .map(|(_, advantage)| *advantage)
+        {
+            all.push(advantage);
+        }
+
+        if !all.is_empty() && !root.takes_advantage() {
+            return Err(DiceError::AdvantageWithoutD20);
+        }
+        Ok(Advantage::resolve(&all))
+    }
+
+    // A check against the total of the whole expression, e.g. "vs AC 17"
+    fn parse_target(&mut self) -> Result<Option<Target>, DiceError> {
+        let op = if self.take_keyword("vs") {
+            let op = self.peek_compare_op();
+            if op.is_some() {
+                self.next();
+            }
+            op.unwrap_or(CompareOp::GreaterOrEqual)
+        } else if let Some(op) = self.peek_compare_op() {
+            self.next();
+            op
+        } else {
+            return Ok(None);
+        };
+
+        let kind = if self.take_keyword("ac") {
+            Some(TargetKind::Ac)
+        } else if self.take_keyword("dc") {
+            Some(TargetKind::Dc)
+        } else {
+            None
+        };
+
+        match self.take_signed_number()? {
+            Some(value) => Ok(Some(Target::new(Comparison::new(op, value), kind))),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    // Whether the comparison at the current token is followed by "ac" or "dc",
+    // making it a target for the whole roll rather than a dice pool
+    fn peek_target_kind(&self) -> bool {
+        matches!(
+            self.tokens.get(self.pos + 1),
+            Some(Token { kind: TokenKind::Word(word), .. }) if word.starts_with("ac") || word.starts_with("dc")
+        )
+    }
+
+    fn peek_compare_op(&self) -> Option<CompareOp> {
+        match self.peek_kind() {
+            Some(TokenKind::Equal) => Some(CompareOp::Equal),
+            Some(TokenKind::Less) => Some(CompareOp::Less),
+            Some(TokenKind::LessEqual) => Some(CompareOp::LessOrEqual),
+            Some(TokenKind::Greater) => Some(CompareOp::Greater),
+            Some(TokenKind::GreaterEqual) => Some(CompareOp::GreaterOrEqual),
+            _ => None,
+        }
+    }
+
+    // An optional face comparison; a bare number means "equal to"
+    fn parse_comparison(&mut self) -> Result<Option<Comparison>, DiceError> {
+        let op = match (self.peek_compare_op(), self.peek_kind()) {
+            (Some(op), _) => op,
+            (None, Some(TokenKind::Number(n))) => {
+                let n = *n;
+                let position = self.next().map_or(0, |token| token.position);
+                let value = i32::try_from(n).map_err(|_| DiceError::NumberTooLarge { position })?;
+                return Ok(Some(Comparison::new(CompareOp::Equal, value)));
+            },
+            _ => return Ok(None),
+        };
+        self.next();
+
+        match self.take_signed_number()? {
+            Some(value) => Ok(Some(Comparison::new(op, value))),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    // An integer with an optional leading minus, as in "d{-1,0,1}" or "=-1"
+    fn take_signed_number(&mut self) -> Result<Option<i32>, DiceError> {
+        let negative = self.peek_kind() == Some(&TokenKind::Minus);
+        if negative {
+            self.next();
+        }
+        let position = self.peek().map_or(0, |token| token.position);
+        let Some(n) = self.take_number() else {
+            return if negative { Err(self.unexpected()) } else { Ok(None) };
+        };
+        let value = i64::from(n);
+        i32::try_from(if negative { -value } else { value })
+            .map(Some)
+            .map_err(|_| DiceError::NumberTooLarge { position })
+    }
+
+    fn take_number(&mut self) -> Option<u32> {
+        match self.peek_kind() {
+            Some(TokenKind::Number(n)) => {
+                let n = *n;
+                self.next();
+                Some(n)
+            },
+            _ => None,
+        }
+    }
+
+    // Consumes the "d" of dice, but only when faces come after it: a number, "f",
+    // "%" or "{". Anything else, such as "dis", is left for the caller.
+    fn take_dice(&mut self) -> bool {
+        let faces_follow = match self.peek_kind() {
+            Some(TokenKind::Word(word)) if word == "d" => matches!(
+                self.tokens.get(self.pos + 1).map(|token| &token.kind),
+                Some(TokenKind::Number(_) | TokenKind::Percent | TokenKind::LeftBrace)
+            ),
+            Some(TokenKind::Word(word)) => word.starts_with("df"),
+            _ => false,
+        };
+        faces_follow && self.take_keyword("d")
+    }
+
+    // Consumes keyword from the start of the current word, leaving any remaining letters as a new word
+    fn take_keyword(&mut self, keyword: &str) -> bool {
+        let Some(Token { kind: TokenKind::Word(word), position }) = self.tokens.get_mut(self.pos) else {
+            return false;
+        };
+        let Some(rest) = word.strip_prefix(keyword) else {
+            return false;
+        };
+
+        if rest.is_empty() {
+            self.pos += 1;
+        } else {
+            *word = rest.to_string();
+            *position += keyword.len();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::MAX_REPEAT;
+
+    #[test]
+    fn comparisons_after_dice_count_successes() {
+        let pool = parse("2d20>=15").unwrap();
+        assert_eq!(pool.target, None);
+        let ExpressionNode::Roll(roll) = &pool.root else { panic!("{:?}", pool.root) };
+        assert!(matches!(roll.operations.as_slice(), [DiceRollOp::CountSuccesses { failure: None, .. }]));
+
+        // A single die isn't a pool, so its comparison is a target
+        let target = Target::new(Comparison::new(CompareOp::GreaterOrEqual, 15), None);
+        for input in ["1d20>=15", "d20 >= 15", "1d20 vs 15", "1d20 vs >=15", "1d20+0>=15"] {
+            assert_eq!(parse(input).unwrap().target, Some(target), "{}", input);
+        }
+    }
+
+    #[test]
+    fn advantage_after_a_modifier() {
+        for (input, advantage) in [
+            ("1d20+5 adv", Advantage::Advantage),
+            ("1d20+5 dis", Advantage::Disadvantage),
+            ("1d20 - 5 dis", Advantage::Disadvantage),
+            ("1d20+5 disadvantage", Advantage::Disadvantage),
+            ("1d20+5 advantage", Advantage::Advantage),
+            ("1d20+5 ea", Advantage::ElvenAccuracy),
+        ] {
+            let expression = parse(input).unwrap();
+            assert_eq!(expression.advantage, Some(advantage), "{}", input);
+            assert!(expression.target.is_none(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn advantage_before_a_target() {
+        for (input, advantage) in [
+            ("d20+3 adv vs 15", Advantage::Advantage),
+            ("d20+3 dis vs 15", Advantage::Disadvantage),
+            ("d20+3 ea vs AC 15", Advantage::ElvenAccuracy),
+        ] {
+            let expression = parse(input).unwrap();
+            assert_eq!(expression.advantage, Some(advantage), "{}", input);
+            assert_eq!(expression.target.map(|target| target.comparison.value), Some(15), "{}", input);
+        }
+    }
+
+    #[test]
+    fn advantage_and_disadvantage_cancel_out() {
+        assert_eq!(parse("1d20+5 adv dis").unwrap().advantage, None);
+        assert_eq!(parse("1d20+5 ea dis").unwrap().advantage, None);
+    }
+
+    #[test]
+    fn advantage_needs_a_single_d20() {
+        assert_eq!(parse("2d6+5 adv"), Err(DiceError::AdvantageWithoutD20));
+        assert_eq!(parse("5 dis"), Err(DiceError::AdvantageWithoutD20));
+        // Only one d20 gets it, and never one that already keeps or drops
+        assert_eq!(parse("1d20+1d20 adv"), Err(DiceError::AdvantageWithoutD20));
+        assert_eq!(parse("d20kh1 adv"), Err(DiceError::AdvantageWithoutD20));
+        assert_eq!(parse("{1d20, 1d20}kh1 adv"), Err(DiceError::AdvantageWithoutD20));
+        assert!(parse("1d20+2d20 adv").is_ok());
+    }
+
+    #[test]
+    fn d_only_starts_dice_before_faces() {
+        assert!(parse("2d6").is_ok());
+        assert!(parse("4dF").is_ok());
+        assert!(parse("d%").is_ok());
+        assert!(parse("1d{1,2,3}").is_ok());
+        assert!(matches!(parse("5 d"), Err(DiceError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn dice_shorthand_and_faces() {
+        assert_eq!(parse("d20").unwrap().to_string(), "1d20");
+        assert_eq!(parse("4DF").unwrap().to_string(), "4dF");
+        assert_eq!(parse("2d%").unwrap().to_string(), "2d%");
+        assert_eq!(parse("3d{2, 4, -6}").unwrap().to_string(), "3d{2,4,-6}");
+        assert_eq!(parse("4d6!pkh3").unwrap().to_string(), "4d6!pkh3");
+    }
+
+    #[test]
+    fn repeats_and_descriptions() {
+        let expression = parse("6x 4d6dl1 # Ability scores").unwrap();
+        assert_eq!(expression.repeat, Some(6));
+        assert_eq!(expression.description.as_deref(), Some("Ability scores"));
+        assert_eq!(parse("0x 1d6"), Err(DiceError::RepeatOutOfRange { repeat: 0, max: MAX_REPEAT }));
+        assert_eq!(parse("# nothing"), Err(DiceError::EmptyExpression));
+        assert_eq!(parse(""), Err(DiceError::EmptyExpression));
+    }
+
+    #[test]
+    fn variables() {
+        let mut variables = Variables::new();
+        variables.set("Str", 3);
+        let expression = parse_with_variables("1d20+@STR", &variables).unwrap();
+        assert_eq!(expression.to_string(), "1d20+@str");
+        assert_eq!(
+            parse_with_variables("1d20+@dex", &variables),
+            Err(DiceError::UnknownVariable { name: String::from("dex"), position: 5 })
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(parse("1d20+"), Err(DiceError::UnexpectedEnd));
+        assert_eq!(parse("(1d20"), Err(DiceError::UnexpectedEnd));
+        assert_eq!(parse("1d20 2"), Err(DiceError::UnexpectedToken { token: String::from("2"), position: 5 }));
+        assert_eq!(parse("1dx"), Err(DiceError::UnexpectedToken { token: String::from("dx"), position: 1 }));
+        assert_eq!(parse("1d20 $"), Err(DiceError::UnexpectedCharacter { character: '$', position: 5 }));
+        assert_eq!(parse("3000000000"), Err(DiceError::NumberTooLarge { position: 0 }));
+    }
+
+    #[test]
+    fn limits_are_checked_while_parsing() {
+        let limits = DiceLimits::server();
+        let variables = Variables::default();
+        let long = "1+".repeat(100) + "1";
+        assert_eq!(
+            parse_with_limits(&long, &variables, &limits),
+            Err(DiceError::ExpressionTooLong { length: 201, max: 200 })
+        );
+        let deep = "(".repeat(20) + "1" + &")".repeat(20);
+        assert_eq!(parse_with_limits(&deep, &variables, &limits), Err(DiceError::NestedTooDeeply { max: 16 }));
+        assert_eq!(parse_with_limits("-".repeat(20).as_str(), &variables, &limits), Err(DiceError::NestedTooDeeply { max: 16 }));
+        assert_eq!(
+            parse_with_limits("21x 1d6", &variables, &limits),
+            Err(DiceError::RepeatOutOfRange { repeat: 21, max: 20 })
+        );
+    }
+}
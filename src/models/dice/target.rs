@@ -0,0 +1,127 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::{CompareOp, Comparison};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKind {
+    Dc, // Difficulty class of a check or save
+    Ac, // Armor class, so natural 20s and 1s decide the attack on their own
+}
+
+impl fmt::Display for TargetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetKind::Dc => write!(f, "DC"),
+            TargetKind::Ac => write!(f, "AC"),
+        }
+    }
+}
+
+// The number a whole roll is checked against, e.g. the "vs AC 17" in "1d20+7 vs AC 17"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    pub comparison: Comparison,
+    pub kind: Option<TargetKind>,
+}
+
+impl Target {
+    pub fn new(comparison: Comparison, kind: Option<TargetKind>) -> Self {
+        Self { comparison, kind }
+    }
+
+    // The usual "meet or beat", as in "vs DC 15"
+    pub fn at_least(value: i32, kind: Option<TargetKind>) -> Self {
+        Self::new(Comparison::new(CompareOp::GreaterOrEqual, value), kind)
+    }
+
+    // Natural criticals and fumbles only override the total on attack rolls
+    pub fn evaluate(&self, total: i32, critical: bool, fumble: bool) -> TargetOutcome {
+        // How far the total cleared the lowest passing value, negative when it fell
+        // short. Roll-under targets count the other way.
+        let value = self.comparison.value;
+        let margin = match self.comparison.op {
+            CompareOp::Less => value.saturating_sub(total).saturating_sub(1),
+            CompareOp::LessOrEqual => value.saturating_sub(total),
+            CompareOp::Greater => total.saturating_sub(value).saturating_sub(1),
+            CompareOp::GreaterOrEqual | CompareOp::Equal => total.saturating_sub(value),
+        };
+
+        let natural = match self.kind {
+            Some(TargetKind::Ac) if critical => Some(true),
+            Some(TargetKind::Ac) if fumble => Some(false),
+            _ => None,
+        };
+
+        TargetOutcome {
+            success: natural.unwrap_or_else(|| self.comparison.matches(total)),
+            margin,
+            automatic: natural.is_some(),
+        }
+    }
+}
+
+// Expression notation: "vs AC 17" for the usual ">=", with the operator
+// otherwise, e.g. "vs < 12". The "vs" keeps a comparison straight after dice
+// from reading back as a dice pool.
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.comparison.op {
+            CompareOp::GreaterOrEqual => write!(f, "vs")?,
+            op => write!(f, "vs {}", op)?,
+        }
+        if let Some(kind) = self.kind {
+            write!(f, " {}", kind)?;
+        }
+        write!(f, " {}", self.comparison.value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetOutcome {
+    pub success: bool,
+    pub margin: i32,
+    pub automatic: bool, // Decided by a natural critical or fumble rather than the total
+}
+
+// "PASS (+5)", "FAIL (-3)" or "PASS (-2, natural)"
+impl fmt::Display for TargetOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.success { "PASS" } else { "FAIL" };
+        let natural = if self.automatic { ", natural" } else { "" };
+        write!(f, "{} ({:+}{})", verdict, self.margin, natural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn margins() {
+        let dc = Target::at_least(15, Some(TargetKind::Dc));
+        assert_eq!(dc.evaluate(18, false, false), TargetOutcome { success: true, margin: 3, automatic: false });
+        assert_eq!(dc.evaluate(12, false, false), TargetOutcome { success: false, margin: -3, automatic: false });
+
+        let under = Target::new(Comparison::new(CompareOp::Less, 12), None);
+        assert_eq!(under.evaluate(9, false, false), TargetOutcome { success: true, margin: 2, automatic: false });
+        assert_eq!(under.evaluate(12, false, false), TargetOutcome { success: false, margin: -1, automatic: false });
+    }
+
+    #[test]
+    fn naturals_only_decide_attacks() {
+        let ac = Target::at_least(25, Some(TargetKind::Ac));
+        assert_eq!(ac.evaluate(22, true, false), TargetOutcome { success: true, margin: -3, automatic: true });
+        assert_eq!(ac.evaluate(30, false, true), TargetOutcome { success: false, margin: 5, automatic: true });
+
+        let dc = Target::at_least(25, Some(TargetKind::Dc));
+        assert!(!dc.evaluate(22, true, false).success);
+    }
+
+    #[test]
+    fn notation() {
+        assert_eq!(Target::at_least(17, Some(TargetKind::Ac)).to_string(), "vs AC 17");
+        assert_eq!(Target::new(Comparison::new(CompareOp::Less, 12), None).to_string(), "vs < 12");
+        assert_eq!(TargetOutcome { success: true, margin: 5, automatic: false }.to_string(), "PASS (+5)");
+    }
+}
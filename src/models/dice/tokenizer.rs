@@ -0,0 +1,215 @@
+use std::fmt;
+use super::DiceError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Number(u32),
+    // Runs of letters such as "d", "kh" or "kl", always lowercased
+    Word(String),
+    // Text between square brackets, as typed, e.g. the "STR" in "5[STR]". A
+    // backslash keeps the next character in the label, as in "[a\]b]".
+    Label(String),
+    // A "@name" reference, lowercased and without the "@"
+    Variable(String),
+    // Everything after a "#", trimmed
+    Description(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Percent,
+    Bang,
+    Equal,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub position: usize, // Byte offset of the token in the source expression
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "{}", n),
+            TokenKind::Word(w) => write!(f, "{}", w),
+            TokenKind::Label(label) => write!(f, "{}", escape_label(label)),
+            TokenKind::Variable(name) => write!(f, "@{}", name),
+            TokenKind::Description(description) => write!(f, "# {}", description),
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Minus => write!(f, "-"),
+            TokenKind::Star => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::LeftParen => write!(f, "("),
+            TokenKind::RightParen => write!(f, ")"),
+            TokenKind::LeftBrace => write!(f, "{{"),
+            TokenKind::RightBrace => write!(f, "}}"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Percent => write!(f, "%"),
+            TokenKind::Bang => write!(f, "!"),
+            TokenKind::Equal => write!(f, "="),
+            TokenKind::Less => write!(f, "<"),
+            TokenKind::LessEqual => write!(f, "<="),
+            TokenKind::Greater => write!(f, ">"),
+            TokenKind::GreaterEqual => write!(f, ">="),
+        }
+    }
+}
+
+// A label in brackets, with any "]" or backslash in it escaped so the label
+// reads back as it was, e.g. "a]b" becomes "[a\]b]"
+pub fn escape_label(label: &str) -> String {
+    let mut escaped = String::from("[");
+    for c in label.chars() {
+        if matches!(c, ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped.push(']');
+    escaped
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, DiceError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit() {
+            let mut value: u32 = 0;
+            while let Some(&(_, d)) = chars.peek() {
+                let Some(digit) = d.to_digit(10) else { break };
+                value = value.checked_mul(10)
+                    .and_then(|v| v.checked_add(digit))
+                    .ok_or(DiceError::NumberTooLarge { position })?;
+                chars.next();
+            }
+            TokenKind::Number(value)
+        } else if c.is_ascii_alphabetic() {
+            let mut word = String::new();
+            while let Some(&(_, l)) = chars.peek() {
+                if !l.is_ascii_alphabetic() {
+                    break;
+                }
+                word.push(l.to_ascii_lowercase());
+                chars.next();
+            }
+            TokenKind::Word(word)
+        } else if c == '[' {
+            chars.next();
+            let mut label = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, ']')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, l)) => label.push(l),
+                        None => return Err(DiceError::UnexpectedEnd),
+                    },
+                    Some((_, l)) => label.push(l),
+                    None => return Err(DiceError::UnexpectedEnd),
+                }
+            }
+            TokenKind::Label(label.trim().to_string())
+        } else if c == '@' {
+            chars.next();
+            let mut name = String::new();
+            while let Some(&(_, l)) = chars.peek() {
+                if !(l.is_ascii_alphanumeric() || l == '_') {
+                    break;
+                }
+                name.push(l.to_ascii_lowercase());
+                chars.next();
+            }
+            if name.is_empty() {
+                return Err(DiceError::UnexpectedCharacter { character: c, position });
+            }
+            TokenKind::Variable(name)
+        } else if c == '#' {
+            let description: String = chars.by_ref().skip(1).map(|(_, l)| l).collect();
+            TokenKind::Description(description.trim().to_string())
+        } else {
+            chars.next();
+            match c {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '(' => TokenKind::LeftParen,
+                ')' => TokenKind::RightParen,
+                '{' => TokenKind::LeftBrace,
+                '}' => TokenKind::RightBrace,
+                ',' => TokenKind::Comma,
+                '%' => TokenKind::Percent,
+                '!' => TokenKind::Bang,
+                '=' => TokenKind::Equal,
+                '<' | '>' => {
+                    let or_equal = chars.next_if(|&(_, next)| next == '=').is_some();
+                    match (c, or_equal) {
+                        ('<', false) => TokenKind::Less,
+                        ('<', true) => TokenKind::LessEqual,
+                        ('>', false) => TokenKind::Greater,
+                        _ => TokenKind::GreaterEqual,
+                    }
+                },
+                _ => return Err(DiceError::UnexpectedCharacter { character: c, position }),
+            }
+        };
+
+        tokens.push(Token { kind, position });
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        tokenize(input).unwrap().into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn dice_and_operators() {
+        assert_eq!(kinds("2D6 + 3"), vec![
+            TokenKind::Number(2), TokenKind::Word(String::from("d")), TokenKind::Number(6),
+            TokenKind::Plus, TokenKind::Number(3),
+        ]);
+        assert_eq!(kinds(">=<=<>!"), vec![
+            TokenKind::GreaterEqual, TokenKind::LessEqual, TokenKind::Less, TokenKind::Greater, TokenKind::Bang,
+        ]);
+    }
+
+    #[test]
+    fn labels_variables_and_descriptions() {
+        assert_eq!(kinds("[ Fire ]@Str_mod# Sneak attack "), vec![
+            TokenKind::Label(String::from("Fire")),
+            TokenKind::Variable(String::from("str_mod")),
+            TokenKind::Description(String::from("Sneak attack")),
+        ]);
+        assert_eq!(kinds(r"[a\]b\\c]"), vec![TokenKind::Label(String::from(r"a]b\c"))]);
+        assert_eq!(escape_label(r"a]b\c"), r"[a\]b\\c]");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(tokenize("[open"), Err(DiceError::UnexpectedEnd));
+        assert_eq!(tokenize("1 & 2"), Err(DiceError::UnexpectedCharacter { character: '&', position: 2 }));
+        assert_eq!(tokenize("@"), Err(DiceError::UnexpectedCharacter { character: '@', position: 0 }));
+        assert_eq!(tokenize("99999999999"), Err(DiceError::NumberTooLarge { position: 0 }));
+    }
+}
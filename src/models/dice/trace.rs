@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+// One step of a roll's evaluation, with the steps that fed into it. Shown as
+// an expandable tree, e.g. "15 + 5 = 20" opening up into "1d20 = 15" and "5".
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceNode {
+    pub label: String,
+    pub value: Option<i32>, // None for steps that don't produce a number, such as a keep
+    pub children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    pub fn leaf(label: impl Into<String>, value: Option<i32>) -> Self {
+        Self { label: label.into(), value, children: Vec::new() }
+    }
+
+    pub fn branch(label: impl Into<String>, value: i32, children: Vec<TraceNode>) -> Self {
+        Self { label: label.into(), value: Some(value), children }
+    }
+
+    // Prefixes an inline label, e.g. "STR: 1d20"
+    pub fn labeled(mut self, label: &str) -> Self {
+        self.label = format!("{}: {}", label, self.label);
+        self
+    }
+}
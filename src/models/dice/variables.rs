@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+// Named values an expression can refer to as "@name", e.g. a character's "@dex" or "@prof"
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variables {
+    pub values: BTreeMap<String, i32>, // Keyed by lowercase name, without the "@"
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Names are case-insensitive, so "@DEX" and "@dex" are the same variable
+    pub fn normalize_name(name: &str) -> String {
+        name.trim().trim_start_matches('@').to_ascii_lowercase()
+    }
+
+    // A usable name is non-empty and only letters, digits and underscores
+    pub fn is_valid_name(name: &str) -> bool {
+        let name = Self::normalize_name(name);
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    pub fn get(&self, name: &str) -> Option<i32> {
+        self.values.get(&Self::normalize_name(name)).copied()
+    }
+
+    pub fn set(&mut self, name: &str, value: i32) {
+        self.values.insert(Self::normalize_name(name), value);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<i32> {
+        self.values.remove(&Self::normalize_name(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.values.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
@@ -1,8 +1,18 @@
 pub mod damage;
 pub mod dice;
+pub mod probability;
+pub mod random_table;
 pub mod roll_history;
+pub mod serialization;
+pub mod simulation;
+pub mod variables;
 
 // Re-export common types
 pub use damage::{DamageType, Damage};
-pub use dice::{DiceRoll, DiceRollResult};
-pub use roll_history::{DiceHistoryEntry, DiceHistoryStore};
\ No newline at end of file
+pub use dice::{DiceExpression, DiceExpressionResult, DiceRoll, DiceRollResult, Variables};
+pub use probability::{Distribution, DistributionError};
+pub use random_table::{RandomTable, TableEntry, TableError, TableRoll, TableSet};
+pub use roll_history::{DiceHistoryEntry, DiceHistoryStore};
+pub use serialization::{from_json, to_json, SerializationError, Versioned, MIN_SCHEMA_VERSION, SCHEMA_VERSION};
+pub use simulation::{ConfidenceInterval, Simulation};
+pub use variables::VariableStore;
\ No newline at end of file
@@ -0,0 +1,596 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::models::dice::{BinaryOp, DiceError, DiceExpression, DiceRoll, DiceRollOp, Die, DieFaces, ExpressionNode, Fraction, Function};
+
+// Upper bound on the inner-loop steps spent on one distribution, so a huge
+// pool like "1000d1000kh500" fails fast instead of freezing the page
+const MAX_WORK: u64 = 50_000_000;
+
+// Most totals one distribution may hold, e.g. the million faces of a d1000000,
+// so a single huge die can't take all the memory on its own
+const MAX_OUTCOMES: u64 = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DistributionError {
+    Unsupported(String), // No closed form for this operation, simulate it instead
+    TooComplex,
+    Evaluation(DiceError),
+}
+
+impl fmt::Display for DistributionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistributionError::Unsupported(what) => write!(f, "Exact odds aren't available for {}", what),
+            DistributionError::TooComplex => write!(f, "Expression is too large to compute exact odds"),
+            DistributionError::Evaluation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DistributionError {}
+
+// Probability mass function over the possible totals
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    // Every possible total with its probability, lowest first. Impossible totals
+    // are left out, so "1d20*1000" holds 20 outcomes rather than 20,000.
+    outcomes: Vec<(i32, f64)>,
+}
+
+impl Distribution {
+    pub fn constant(value: i32) -> Self {
+        Self { outcomes: vec![(value, 1.0)] }
+    }
+
+    // A fair die numbered low..=high
+    fn uniform(low: i32, high: i32, work: &mut u64) -> Result<Self, DistributionError> {
+        let count = (i64::from(high) - i64::from(low) + 1) as u64;
+        if count > MAX_OUTCOMES {
+            return Err(DistributionError::TooComplex);
+        }
+        charge(work, count)?;
+        Ok(Self { outcomes: (low..=high).map(|value| (value, 1.0 / count as f64)).collect() })
+    }
+
+    // Keeps the outcomes, already sorted by total, that can actually happen
+    fn from_outcomes(outcomes: impl IntoIterator<Item = (i32, f64)>) -> Self {
+        let outcomes: Vec<(i32, f64)> = outcomes.into_iter().filter(|(_, p)| *p > 0.0).collect();
+        if outcomes.is_empty() {
+            return Self::constant(0);
+        }
+        Self { outcomes }
+    }
+
+    fn from_map(map: BTreeMap<i32, f64>) -> Self {
+        Self::from_outcomes(map)
+    }
+
+    // Adds up the probability of each total, where every total lies within
+    // low..=high. Narrow ranges are added up in place; wide ones go through a
+    // map, so memory follows the number of outcomes rather than their range.
+    fn accumulate(low: i32, high: i32, outcomes: impl Iterator<Item = (i32, f64)>) -> Result<Self, DistributionError> {
+        let span = (i64::from(high) - i64::from(low) + 1) as u64;
+        if span <= 4 * MAX_OUTCOMES {
+            let mut probabilities = vec![0.0; span as usize];
+            for (value, p) in outcomes {
+                probabilities[(i64::from(value) - i64::from(low)) as usize] += p;
+            }
+            let distribution = Self::from_outcomes((low..=high).zip(probabilities));
+            if distribution.len() as u64 > MAX_OUTCOMES {
+                return Err(DistributionError::TooComplex);
+            }
+            return Ok(distribution);
+        }
+
+        let mut map = BTreeMap::new();
+        for (value, p) in outcomes {
+            add_outcome(&mut map, value, p)?;
+        }
+        Ok(Self::from_map(map))
+    }
+
+    // Observed frequencies, e.g. from a simulation, normalised into probabilities
+    pub fn from_counts(counts: &BTreeMap<i32, u64>) -> Self {
+        let total: u64 = counts.values().sum();
+        if total == 0 {
+            return Self::constant(0);
+        }
+        Self::from_outcomes(counts.iter().map(|(value, count)| (*value, *count as f64 / total as f64)))
+    }
+
+    // A single roll of the die, each face equally likely
+    pub fn of_die(die: &Die) -> Result<Self, DistributionError> {
+        let mut work = 0;
+        of_face(die, &mut work)
+    }
+
+    pub fn of_expression(expression: &DiceExpression) -> Result<Self, DistributionError> {
+        let mut work = 0;
+        let root = expression.root_with_advantage().map_err(DistributionError::Evaluation)?;
+        of_node(&root, &mut work)
+    }
+
+    pub fn of_dice_roll(dice: &DiceRoll) -> Result<Self, DistributionError> {
+        let mut work = 0;
+        of_dice(dice, &mut work)
+    }
+
+    // (value, probability) pairs for every possible total, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        self.outcomes.iter().copied()
+    }
+
+    // How many different totals are possible
+    pub fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+
+    pub fn probability(&self, value: i32) -> f64 {
+        self.outcomes.binary_search_by_key(&value, |(v, _)| *v)
+            .map_or(0.0, |i| self.outcomes[i].1)
+    }
+
+    pub fn min(&self) -> i32 {
+        self.outcomes.first().map_or(0, |(v, _)| *v)
+    }
+
+    pub fn max(&self) -> i32 {
+        self.outcomes.last().map_or(0, |(v, _)| *v)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(v, p)| f64::from(v) * p).sum()
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        self.iter()
+            .map(|(v, p)| (f64::from(v) - mean).powi(2) * p)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    // P(result >= value)
+    pub fn at_least(&self, value: i32) -> f64 {
+        let start = self.outcomes.partition_point(|(v, _)| *v < value);
+        self.outcomes[start..].iter()
+            .fold(0.0, |total, (_, p)| total + p)
+            .min(1.0)
+    }
+
+    // Smallest total whose cumulative probability reaches `fraction`, e.g. 0.5 for the median
+    pub fn percentile(&self, fraction: f64) -> i32 {
+        let mut cumulative = 0.0;
+        for (value, p) in self.iter() {
+            cumulative += p;
+            // A little slack so rounding error can't push the median past its value
+            if cumulative >= fraction - 1e-12 {
+                return value;
+            }
+        }
+        self.max()
+    }
+
+    fn negate(&self) -> Result<Self, DistributionError> {
+        let outcomes = self.outcomes.iter().rev()
+            .map(|(v, p)| v.checked_neg().map(|v| (v, *p)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(DistributionError::Evaluation(DiceError::Overflow))?;
+        Ok(Self { outcomes })
+    }
+
+    // Distribution of the sum of two independent totals
+    fn add(&self, other: &Self, work: &mut u64) -> Result<Self, DistributionError> {
+        charge(work, self.len() as u64 * other.len() as u64)?;
+
+        let overflow = DistributionError::Evaluation(DiceError::Overflow);
+        let low = self.min().checked_add(other.min()).ok_or(overflow.clone())?;
+        let high = self.max().checked_add(other.max()).ok_or(overflow)?;
+        let sums = self.iter().flat_map(|(a, pa)| other.iter().map(move |(b, pb)| (a + b, pa * pb)));
+        Self::accumulate(low, high, sums)
+    }
+
+    // Any other pairwise combination, evaluated value by value
+    fn combine(&self, other: &Self, work: &mut u64, f: impl Fn(i32, i32) -> Result<i32, DiceError>) -> Result<Self, DistributionError> {
+        charge(work, self.len() as u64 * other.len() as u64)?;
+
+        let mut map = BTreeMap::new();
+        for (a, pa) in self.iter() {
+            for (b, pb) in other.iter() {
+                let value = f(a, b).map_err(DistributionError::Evaluation)?;
+                add_outcome(&mut map, value, pa * pb)?;
+            }
+        }
+        Ok(Self::from_map(map))
+    }
+}
+
+fn charge(work: &mut u64, amount: u64) -> Result<(), DistributionError> {
+    *work = work.saturating_add(amount);
+    if *work > MAX_WORK {
+        return Err(DistributionError::TooComplex);
+    }
+    Ok(())
+}
+
+// Adds to the probability of one total, refusing to hold more than MAX_OUTCOMES
+fn add_outcome<K: Ord>(map: &mut BTreeMap<K, f64>, value: K, p: f64) -> Result<(), DistributionError> {
+    *map.entry(value).or_insert(0.0) += p;
+    if map.len() as u64 > MAX_OUTCOMES {
+        return Err(DistributionError::TooComplex);
+    }
+    Ok(())
+}
+
+fn of_face(die: &Die, work: &mut u64) -> Result<Distribution, DistributionError> {
+    match die.faces {
+        DieFaces::Numbered(sides) => {
+            let sides = i32::try_from(sides).map_err(|_| DistributionError::TooComplex)?;
+            Distribution::uniform(1, sides, work)
+        },
+        DieFaces::Percentile => Distribution::uniform(1, 100, work),
+        _ => {
+            let mut map = BTreeMap::new();
+            for value in die.face_values() {
+                *map.entry(value).or_insert(0.0) += 1.0 / f64::from(die.sides());
+            }
+            Ok(Distribution::from_map(map))
+        },
+    }
+}
+
+fn of_node(node: &ExpressionNode, work: &mut u64) -> Result<Distribution, DistributionError> {
+    match node {
+        ExpressionNode::Roll(dice) => of_dice(dice, work),
+        ExpressionNode::Constant(value) | ExpressionNode::Variable { value, .. } => Ok(Distribution::constant(*value)),
+        ExpressionNode::Negate(inner) => of_node(inner, work)?.negate(),
+        ExpressionNode::Labeled { inner, .. } => of_node(inner, work),
+        ExpressionNode::Group { items, keep } => of_group(items, keep.as_ref(), work),
+        ExpressionNode::Function { function, args } => of_function(*function, args, work),
+        ExpressionNode::Binary { op, lhs, rhs } => {
+            let lhs = of_node(lhs, work)?;
+            let rhs = of_node(rhs, work)?;
+            match op {
+                BinaryOp::Add => lhs.add(&rhs, work),
+                BinaryOp::Subtract => lhs.add(&rhs.negate()?, work),
+                _ => lhs.combine(&rhs, work, |a, b| op.apply(a, b)),
+            }
+        },
+    }
+}
+
+fn of_function(function: Function, args: &[ExpressionNode], work: &mut u64) -> Result<Distribution, DistributionError> {
+    // A rounding function rounds the exact value of its argument, as when rolling
+    if let (Some(rounding), [arg]) = (function.rounding(), args) {
+        let mut map = BTreeMap::new();
+        for (exact, p) in of_exact(arg, work)? {
+            let value = rounding.round(exact).map_err(DistributionError::Evaluation)?;
+            add_outcome(&mut map, value, p)?;
+        }
+        return Ok(Distribution::from_map(map));
+    }
+
+    let Some((first, rest)) = args.split_first() else {
+        return Err(DistributionError::Evaluation(DiceError::EmptyExpression));
+    };
+    let mut total = of_node(first, work)?;
+    match function {
+        Function::Min | Function::Max => {
+            for arg in rest {
+                let arg = of_node(arg, work)?;
+                total = total.combine(&arg, work, |a, b| function.apply(&[a, b]))?;
+            }
+            Ok(total)
+        },
+        _ => total.combine(&Distribution::constant(0), work, |a, _| function.apply(&[a])),
+    }
+}
+
+// Like of_node, but with signs and arithmetic kept exact, so "floor(7/2+1d4/2)"
+// only rounds once. Anything else is worked out as usual.
+fn of_exact(node: &ExpressionNode, work: &mut u64) -> Result<BTreeMap<Fraction, f64>, DistributionError> {
+    match node {
+        ExpressionNode::Labeled { inner, .. } => of_exact(inner, work),
+        ExpressionNode::Negate(inner) => of_exact(inner, work)?.into_iter()
+            .map(|(value, p)| Ok((value.negate().map_err(DistributionError::Evaluation)?, p)))
+            .collect(),
+        ExpressionNode::Binary { op, lhs, rhs } => {
+            let lhs = of_exact(lhs, work)?;
+            let rhs = of_exact(rhs, work)?;
+            charge(work, lhs.len() as u64 * rhs.len() as u64)?;
+
+            let mut map = BTreeMap::new();
+            for (a, pa) in &lhs {
+                for (b, pb) in &rhs {
+                    let value = a.apply(*op, b).map_err(DistributionError::Evaluation)?;
+                    add_outcome(&mut map, value, pa * pb)?;
+                }
+            }
+            Ok(map)
+        },
+        _ => Ok(of_node(node, work)?.iter().map(|(value, p)| (Fraction::from(value), p)).collect()),
+    }
+}
+
+fn of_group(items: &[ExpressionNode], keep: Option<&DiceRollOp>, work: &mut u64) -> Result<Distribution, DistributionError> {
+    let items = items.iter()
+        .map(|item| of_node(item, work))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let count = items.len() as u32;
+    let kept = match keep {
+        Some(DiceRollOp::KeepHighest(n) | DiceRollOp::KeepLowest(n)) => *n,
+        Some(DiceRollOp::DropHighest(n) | DiceRollOp::DropLowest(n)) => count.saturating_sub(*n),
+        _ => count,
+    };
+
+    if kept == count {
+        let mut total = Distribution::constant(0);
+        for item in &items {
+            total = total.add(item, work)?;
+        }
+        return Ok(total);
+    }
+    if kept != 1 {
+        return Err(DistributionError::Unsupported(String::from("keeping several items of a group")));
+    }
+
+    let highest = matches!(keep, Some(DiceRollOp::KeepHighest(_) | DiceRollOp::DropLowest(_)));
+    extreme(&items, highest, work)
+}
+
+// Distribution of the highest (or lowest) of independent totals, from the
+// product of their cumulative probabilities
+fn extreme(items: &[Distribution], highest: bool, work: &mut u64) -> Result<Distribution, DistributionError> {
+    // The highest or lowest is always one of the items' own totals
+    let mut values: Vec<i32> = items.iter().flat_map(|item| item.iter().map(|(value, _)| value)).collect();
+    values.sort_unstable();
+    values.dedup();
+    charge(work, values.len() as u64 * items.iter().map(|item| item.len() as u64).sum::<u64>())?;
+
+    // P(every item is at most v) for the highest, P(every item is at least v) for the lowest
+    let all_within = |value: i32| -> f64 {
+        items.iter()
+            .map(|item| if highest { 1.0 - item.at_least(value.saturating_add(1)) } else { item.at_least(value) })
+            .product()
+    };
+
+    // Walk away from the impossible end, where the cumulative probability starts at zero
+    if !highest {
+        values.reverse();
+    }
+    let mut map = BTreeMap::new();
+    let mut previous = 0.0;
+    for value in values {
+        let cumulative = all_within(value);
+        // Rounding can leave a hair below zero
+        map.insert(value, (cumulative - previous).max(0.0));
+        previous = cumulative;
+    }
+    Ok(Distribution::from_map(map))
+}
+
+fn of_dice(dice: &DiceRoll, work: &mut u64) -> Result<Distribution, DistributionError> {
+    let mut face = of_face(&dice.die, work)?;
+
+    // Keeps and drops narrow down a window of ranks among the dice sorted low to high
+    let count = dice.die_count as usize;
+    let (mut keep_from, mut keep_to) = (0, count);
+    let mut pool = None;
+
+    for op in &dice.operations {
+        match op {
+            DiceRollOp::Reroll { once, condition } => {
+                let rerolled: f64 = face.iter()
+                    .filter(|(v, _)| condition.matches(*v))
+                    .map(|(_, p)| p)
+                    .sum();
+                let outcomes: Vec<(i32, f64)> = face.iter()
+                    .map(|(value, p)| (value, match (once, condition.matches(value)) {
+                        // Rerolled dice land on each face with the face's own probability
+                        (true, true) => p * rerolled,
+                        (true, false) => p * (1.0 + rerolled),
+                        // Rerolling until it sticks spreads the rerolled mass evenly over the rest
+                        (false, true) => 0.0,
+                        (false, false) => p / (1.0 - rerolled),
+                    }))
+                    .collect();
+                face = Distribution::from_outcomes(outcomes);
+            },
+            DiceRollOp::Minimum(n) | DiceRollOp::Maximum(n) => {
+                let mut map = BTreeMap::new();
+                for (value, p) in face.iter() {
+                    let clamped = if matches!(op, DiceRollOp::Minimum(_)) { value.max(*n) } else { value.min(*n) };
+                    *map.entry(clamped).or_insert(0.0) += p;
+                }
+                face = Distribution::from_map(map);
+            },
+            DiceRollOp::KeepHighest(n) => keep_from = keep_to - (*n as usize).min(keep_to - keep_from),
+            DiceRollOp::KeepLowest(n) => keep_to = keep_from + (*n as usize).min(keep_to - keep_from),
+            DiceRollOp::DropHighest(n) => keep_to -= (*n as usize).min(keep_to - keep_from),
+            DiceRollOp::DropLowest(n) => keep_from += (*n as usize).min(keep_to - keep_from),
+            DiceRollOp::CountSuccesses { success, failure } => pool = Some((*success, *failure)),
+            DiceRollOp::Critical(_) | DiceRollOp::Fumble(_) => {},
+            DiceRollOp::Explode { .. } => {
+                return Err(DistributionError::Unsupported("exploding dice".to_string()));
+            },
+        }
+    }
+
+    let keeps_all = keep_from == 0 && keep_to == count;
+
+    let total = if let Some((success, failure)) = pool {
+        if !keeps_all {
+            return Err(DistributionError::Unsupported("dice pools that keep or drop dice".to_string()));
+        }
+        // Each die contributes +1, 0 or -1 on its own
+        let mut map = BTreeMap::new();
+        for (value, p) in face.iter() {
+            let net = i32::from(success.matches(value))
+                - i32::from(failure.is_some_and(|failure| failure.matches(value)));
+            *map.entry(net).or_insert(0.0) += p;
+        }
+        repeat_sum(&Distribution::from_map(map), count, work)?
+    } else if keeps_all {
+        repeat_sum(&face, count, work)?
+    } else {
+        keep_window(&face, count, keep_from, keep_to, work)?
+    };
+
+    match dice.modifier {
+        Some(modifier) if modifier != 0 => total.add(&Distribution::constant(modifier), work),
+        _ => Ok(total),
+    }
+}
+
+// Sum of `count` independent draws from `face`
+fn repeat_sum(face: &Distribution, count: usize, work: &mut u64) -> Result<Distribution, DistributionError> {
+    let mut total = Distribution::constant(0);
+    for _ in 0..count {
+        total = total.add(face, work)?;
+    }
+    Ok(total)
+}
+
+// Sum of the dice ranked keep_from..keep_to (lowest first) out of `count` dice.
+//
+// Walks the faces from lowest to highest, deciding how many dice show each
+// face. Because those dice take the next ranks in sorted order, we always know
+// how many of them land inside the kept window, and the multinomial weight of
+// each choice is built up one binomial coefficient at a time.
+fn keep_window(face: &Distribution, count: usize, keep_from: usize, keep_to: usize, work: &mut u64) -> Result<Distribution, DistributionError> {
+    let faces: Vec<(i32, f64)> = face.iter().filter(|(_, p)| *p > 0.0).collect();
+    let (Some(&(low, _)), Some(&(high, _))) = (faces.first(), faces.last()) else {
+        return Ok(Distribution::constant(0));
+    };
+    let kept = keep_to - keep_from;
+    let sum_low = low.checked_mul(kept as i32).ok_or(DistributionError::TooComplex)?;
+    let sum_high = high.checked_mul(kept as i32).ok_or(DistributionError::TooComplex)?;
+    let sums = (i64::from(sum_high) - i64::from(sum_low) + 1) as usize;
+
+    charge(work, (faces.len() * (count + 1) * (count + 1)) as u64 * sums as u64)?;
+
+    let binomial = binomial_table(count);
+
+    // states[placed][sum - sum_low]: probability weight with `placed` dice assigned so far
+    let mut states = vec![vec![0.0; sums]; count + 1];
+    states[0][0] = 1.0;
+
+    for &(value, p) in &faces {
+        let mut next = vec![vec![0.0; sums]; count + 1];
+        for placed in 0..=count {
+            for (s, weight) in states[placed].iter().enumerate() {
+                if *weight == 0.0 {
+                    continue;
+                }
+                let mut p_pow = 1.0;
+                for j in 0..=(count - placed) {
+                    // Ranks placed..placed+j show `value`; count how many fall in the window
+                    let in_window = (placed + j).min(keep_to).saturating_sub(placed.max(keep_from));
+                    let sum = s as i64 + i64::from(value - low) * in_window as i64;
+                    // The window's sum can't exceed its bounds once every rank is assigned
+                    if let Some(cell) = next[placed + j].get_mut(sum as usize) {
+                        *cell += weight * binomial[count - placed][j] * p_pow;
+                    }
+                    p_pow *= p;
+                }
+            }
+        }
+        states = next;
+    }
+
+    let probabilities = states.pop().unwrap_or_default();
+    Ok(Distribution::from_outcomes((sum_low..=sum_high).zip(probabilities)))
+}
+
+fn binomial_table(n: usize) -> Vec<Vec<f64>> {
+    let mut table = vec![vec![0.0; n + 1]; n + 1];
+    for i in 0..=n {
+        table[i][0] = 1.0;
+        for j in 1..=i {
+            table[i][j] = table[i - 1][j - 1] + if j < i { table[i - 1][j] } else { 0.0 };
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn of(expression: &str) -> Result<Distribution, DistributionError> {
+        Distribution::of_expression(&DiceExpression::from_expression(expression).unwrap())
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn sum_of_two_dice() {
+        let distribution = of("2d6").unwrap();
+        assert_eq!((distribution.min(), distribution.max()), (2, 12));
+        assert_close(distribution.probability(7), 6.0 / 36.0);
+        assert_close(distribution.mean(), 7.0);
+        assert_close(distribution.at_least(11), 3.0 / 36.0);
+        assert_eq!(distribution.percentile(0.5), 7);
+    }
+
+    #[test]
+    fn keep_and_drop() {
+        assert_close(of("2d20kh1").unwrap().mean(), 13.825);
+        assert_close(of("2d20kl1").unwrap().mean(), 7.175);
+        assert_close(of("4d6dl1").unwrap().mean(), 15869.0 / 1296.0);
+        assert_close(of("{1d20, 1d20}kh1").unwrap().mean(), 13.825);
+    }
+
+    #[test]
+    fn advantage() {
+        assert_close(of("1d20+5 adv").unwrap().mean(), 18.825);
+        assert_close(of("1d20+5 dis").unwrap().mean(), 12.175);
+    }
+
+    #[test]
+    fn floor_division() {
+        let distribution = of("-1d3/2").unwrap();
+        assert_eq!(distribution.iter().map(|(value, _)| value).collect::<Vec<_>>(), vec![-2, -1]);
+        assert_close(distribution.probability(-1), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn rounding_is_exact() {
+        // 1d4/2 + 1/2 is 1, 1.5, 2 or 2.5, which rounds up to 1, 2, 2 and 3
+        let distribution = of("ceil(1d4/2+1/2)").unwrap();
+        assert_eq!(distribution.iter().map(|(value, _)| value).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_close(distribution.probability(2), 0.5);
+        assert_eq!(of("ceil((7/2)[half])").unwrap().min(), 4);
+    }
+
+    #[test]
+    fn reroll_once() {
+        // Great Weapon Fighting on a d6: 1s and 2s are rolled again
+        assert_close(of("1d6ro<=2").unwrap().mean(), 4.0 + 1.0 / 6.0);
+    }
+
+    #[test]
+    fn scaled_rolls_only_hold_possible_totals() {
+        assert_eq!(of("1d20*1000000").unwrap().len(), 20);
+        assert_eq!(of("1d2*1000000000").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn huge_dice_are_refused() {
+        assert_eq!(of("1d1000000000"), Err(DistributionError::TooComplex));
+        assert_eq!(of("1d1000000*1d1000000"), Err(DistributionError::TooComplex));
+        assert_eq!(of("1d1000000").unwrap().len(), 1_000_000);
+    }
+
+    #[test]
+    fn exploding_dice_are_unsupported() {
+        assert!(matches!(of("1d6!"), Err(DistributionError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,376 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::models::dice::{scan_inline_rolls, DiceError, DiceExpression, DiceExpressionResult, DiceRng, InlineRollKind, ThreadDiceRng};
+
+// How many tables deep one roll may go through "roll_on" references, so a
+// table that refers back to itself can't roll forever
+pub const MAX_TABLE_DEPTH: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TableError {
+    Json(String),
+    Toml(String),
+    EmptyTable { table: String },
+    DuplicateTable { table: String },
+    UnknownTable { table: String },
+    MixedEntries { table: String },
+    InvalidRange { table: String, low: i32, high: i32 },
+    OverlappingRanges { table: String, value: i32 },
+    NoEntry { table: String, roll: i32 },
+    NestedTooDeeply { max: usize },
+    Dice { table: String, error: DiceError },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Json(message) => write!(f, "Invalid JSON: {}", message),
+            TableError::Toml(message) => write!(f, "Invalid TOML: {}", message),
+            TableError::EmptyTable { table } => write!(f, "Table '{}' has no entries", table),
+            TableError::DuplicateTable { table } => write!(f, "There is more than one table named '{}'", table),
+            TableError::UnknownTable { table } => write!(f, "No table named '{}'", table),
+            TableError::MixedEntries { table } => {
+                write!(f, "Table '{}' mixes ranged and weighted entries", table)
+            },
+            TableError::InvalidRange { table, low, high } => {
+                write!(f, "Table '{}' has an invalid range {}-{}", table, low, high)
+            },
+            TableError::OverlappingRanges { table, value } => {
+                write!(f, "Table '{}' has more than one entry for {}", table, value)
+            },
+            TableError::NoEntry { table, roll } => write!(f, "Table '{}' has no entry for {}", table, roll),
+            TableError::NestedTooDeeply { max } => write!(f, "Tables refer to each other more than {} levels deep", max),
+            TableError::Dice { table, error } => write!(f, "Table '{}': {}", table, error),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+// One row of a table. Ranged entries cover the rolls in their range, e.g. the
+// [1, 4] of a d100 table's "01-04"; weighted entries cover that many rolls in a
+// row, counting from 1, so a weight of 2 is twice as likely as a weight of 1.
+// A table uses one or the other, and entries with neither have a weight of 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableEntry {
+    #[serde(default)]
+    pub result: String, // Dice in the text are rolled too, e.g. "1d4 goblins"
+    #[serde(default)]
+    pub range: Option<[i32; 2]>,
+    #[serde(default)]
+    pub weight: Option<u32>,
+    #[serde(default)]
+    pub roll_on: Vec<String>, // Tables rolled on after this entry, e.g. ["Trinkets"]
+}
+
+// A named table such as "Random Encounters", picked from by rolling its dice
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RandomTable {
+    pub name: String,
+    #[serde(default)]
+    pub dice: Option<String>, // Expression that picks the entry. Defaults to one die covering every entry.
+    pub entries: Vec<TableEntry>,
+}
+
+impl RandomTable {
+    fn is_ranged(&self) -> bool {
+        self.entries.iter().any(|entry| entry.range.is_some())
+    }
+
+    // The range each entry covers, in entry order; weighted entries are laid
+    // out one after the other from 1. Empty ranges (weight 0) are None.
+    pub fn ranges(&self) -> Vec<Option<(i32, i32)>> {
+        if self.is_ranged() {
+            return self.entries.iter().map(|entry| entry.range.map(|[low, high]| (low, high))).collect();
+        }
+
+        let mut next = 1i32;
+        self.entries.iter()
+            .map(|entry| {
+                let weight = i32::try_from(entry.weight.unwrap_or(1)).unwrap_or(i32::MAX);
+                let low = next;
+                next = next.saturating_add(weight);
+                (weight > 0).then(|| (low, next - 1))
+            })
+            .collect()
+    }
+
+    // The expression rolled to pick an entry, e.g. "1d100"
+    pub fn dice_expression(&self) -> Result<DiceExpression, TableError> {
+        let dice = match &self.dice {
+            Some(dice) => dice.clone(),
+            None => {
+                let highest = self.ranges().into_iter().flatten().map(|(_, high)| high).max().unwrap_or(1);
+                format!("1d{}", highest.max(1))
+            },
+        };
+        DiceExpression::from_expression(&dice).map_err(|error| self.dice_error(error))
+    }
+
+    pub fn entry_for(&self, roll: i32) -> Option<&TableEntry> {
+        self.ranges().into_iter()
+            .position(|range| range.is_some_and(|(low, high)| (low..=high).contains(&roll)))
+            .map(|index| &self.entries[index])
+    }
+
+    fn validate(&self) -> Result<(), TableError> {
+        let table = self.name.clone();
+        if self.entries.is_empty() {
+            return Err(TableError::EmptyTable { table });
+        }
+        if self.is_ranged() && self.entries.iter().any(|entry| entry.range.is_none() || entry.weight.is_some()) {
+            return Err(TableError::MixedEntries { table });
+        }
+
+        let mut ranges: Vec<(i32, i32)> = self.ranges().into_iter().flatten().collect();
+        if let Some(&(low, high)) = ranges.iter().find(|(low, high)| low > high) {
+            return Err(TableError::InvalidRange { table, low, high });
+        }
+        ranges.sort();
+        if let Some(pair) = ranges.windows(2).find(|pair| pair[1].0 <= pair[0].1) {
+            return Err(TableError::OverlappingRanges { table, value: pair[1].0 });
+        }
+
+        self.dice_expression().map(|_| ())
+    }
+
+    fn dice_error(&self, error: DiceError) -> TableError {
+        TableError::Dice { table: self.name.clone(), error }
+    }
+}
+
+// Every table loaded from one JSON or TOML document. Tables refer to each
+// other by name, so a document has to contain every table it refers to.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableSet {
+    pub tables: Vec<RandomTable>,
+}
+
+impl TableSet {
+    pub fn from_json(json: &str) -> Result<Self, TableError> {
+        let set: Self = serde_json::from_str(json).map_err(|err| TableError::Json(err.to_string()))?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, TableError> {
+        let set: Self = toml::from_str(toml).map_err(|err| TableError::Toml(err.to_string()))?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    // JSON if it looks like a JSON object, TOML otherwise
+    pub fn parse(text: &str) -> Result<Self, TableError> {
+        if text.trim_start().starts_with('{') {
+            Self::from_json(text)
+        } else {
+            Self::from_toml(text)
+        }
+    }
+
+    // Looks a table up by name, ignoring case and surrounding spaces
+    pub fn get(&self, name: &str) -> Option<&RandomTable> {
+        self.tables.iter().find(|table| table.name.trim().eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn validate(&self) -> Result<(), TableError> {
+        for (index, table) in self.tables.iter().enumerate() {
+            if self.tables[..index].iter().any(|other| other.name.trim().eq_ignore_ascii_case(table.name.trim())) {
+                return Err(TableError::DuplicateTable { table: table.name.clone() });
+            }
+            table.validate()?;
+
+            let references = table.entries.iter().flat_map(|entry| &entry.roll_on);
+            if let Some(unknown) = references.into_iter().find(|name| self.get(name).is_none()) {
+                return Err(TableError::UnknownTable { table: unknown.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn roll(&self, name: &str) -> Result<TableRoll, TableError> {
+        self.roll_with(name, &mut ThreadDiceRng)
+    }
+
+    pub fn roll_with<R: DiceRng + ?Sized>(&self, name: &str, rng: &mut R) -> Result<TableRoll, TableError> {
+        self.roll_nested(name, rng, 0)
+    }
+
+    fn roll_nested<R: DiceRng + ?Sized>(&self, name: &str, rng: &mut R, depth: usize) -> Result<TableRoll, TableError> {
+        if depth >= MAX_TABLE_DEPTH {
+            return Err(TableError::NestedTooDeeply { max: MAX_TABLE_DEPTH });
+        }
+        let table = self.get(name).ok_or_else(|| TableError::UnknownTable { table: name.to_string() })?;
+
+        let roll = table.dice_expression()?
+            .roll_with(rng)
+            .map_err(|error| table.dice_error(error))?;
+        let entry = table.entry_for(roll.result)
+            .ok_or_else(|| TableError::NoEntry { table: table.name.clone(), roll: roll.result })?;
+
+        // Dice in the entry are replaced by what they rolled, e.g. "3 goblins"
+        let mut result = String::new();
+        let mut dice = Vec::new();
+        let mut position = 0;
+        for inline in scan_inline_rolls(&entry.result) {
+            // "+1 to hit" is part of the text in a table, not something to roll
+            let dice_roll = match &inline.roll {
+                Ok(dice_roll) if inline.kind != InlineRollKind::AttackBonus => dice_roll,
+                _ => continue,
+            };
+            let rolled = dice_roll.roll_with(rng).map_err(|error| table.dice_error(error))?;
+
+            result.push_str(&entry.result[position..inline.start]);
+            result.push_str(&rolled.result.to_string());
+            position = inline.end;
+            dice.push(rolled);
+        }
+        result.push_str(&entry.result[position..]);
+
+        let nested = entry.roll_on.iter()
+            .map(|name| self.roll_nested(name, rng, depth + 1))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(TableRoll { table: table.name.clone(), roll, result, dice, nested })
+    }
+}
+
+// What one roll on a table came to, including the tables it went on to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableRoll {
+    pub table: String,
+    pub roll: DiceExpressionResult, // The roll that picked the entry
+    pub result: String, // The entry's text, with its dice replaced by what they rolled
+    pub dice: Vec<DiceExpressionResult>, // The dice rolled for the entry's text, in order
+    pub nested: Vec<TableRoll>,
+}
+
+impl TableRoll {
+    // Every dice roll made, the picking rolls first, then the text's dice, then nested tables
+    pub fn dice_results(&self) -> Vec<DiceExpressionResult> {
+        let mut results = vec![self.roll.clone()];
+        results.extend(self.dice.iter().cloned());
+        results.extend(self.nested.iter().flat_map(|nested| nested.dice_results()));
+        results
+    }
+
+    // The entry's text followed by whatever nested tables added, e.g.
+    // "A tiny chest. Trinkets: a glass eye"
+    pub fn full_result(&self) -> String {
+        let mut parts = vec![self.result.clone()];
+        parts.extend(self.nested.iter().map(|nested| format!("{}: {}", nested.table, nested.full_result())));
+        parts.retain(|part| !part.is_empty());
+
+        // Parts that already end a sentence aren't given a second full stop
+        let mut text = String::new();
+        for part in parts {
+            if !text.is_empty() {
+                text.push_str(if text.ends_with(['.', '!', '?']) { " " } else { ". " });
+            }
+            text.push_str(&part);
+        }
+        text
+    }
+}
+
+// e.g. "Random Encounters (5): 3 goblins"
+impl fmt::Display for TableRoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.table, self.roll.result, self.full_result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::FixedSequenceRng;
+
+    const TABLES: &str = r#"
+[[tables]]
+name = "Encounters"
+dice = "1d6"
+
+[[tables.entries]]
+range = [1, 3]
+result = "Nothing."
+
+[[tables.entries]]
+range = [4, 6]
+result = "1d4+1 goblins"
+roll_on = ["Loot"]
+
+[[tables]]
+name = "Loot"
+
+[[tables.entries]]
+weight = 2
+result = "A copper coin"
+
+[[tables.entries]]
+result = "A silver ring!"
+"#;
+
+    #[test]
+    fn weighted_entries_cover_consecutive_rolls() {
+        let tables = TableSet::from_toml(TABLES).unwrap();
+        let loot = tables.get("loot").unwrap();
+        assert_eq!(loot.ranges(), vec![Some((1, 2)), Some((3, 3))]);
+        assert_eq!(loot.dice_expression().unwrap().to_string(), "1d3");
+        assert_eq!(loot.entry_for(2).map(|entry| entry.result.as_str()), Some("A copper coin"));
+    }
+
+    #[test]
+    fn rolling_fills_in_dice_and_nested_tables() {
+        let tables = TableSet::parse(TABLES).unwrap();
+        // 5 picks goblins, 3 on the d4 makes four of them, 3 on the d3 picks the ring
+        let roll = tables.roll_with("Encounters", &mut FixedSequenceRng::new(vec![5, 3, 3])).unwrap();
+        assert_eq!(roll.result, "4 goblins");
+        assert_eq!(roll.full_result(), "4 goblins. Loot: A silver ring!");
+        assert_eq!(roll.to_string(), "Encounters (5): 4 goblins. Loot: A silver ring!");
+        assert_eq!(roll.dice_results().len(), 3);
+    }
+
+    #[test]
+    fn json_tables() {
+        let json = r#"{"tables": [{"name": "Coin", "entries": [{"result": "Heads"}, {"result": "Tails"}]}]}"#;
+        let tables = TableSet::parse(json).unwrap();
+        let roll = tables.roll_with("coin", &mut FixedSequenceRng::new(vec![2])).unwrap();
+        assert_eq!(roll.result, "Tails");
+    }
+
+    #[test]
+    fn invalid_tables() {
+        let table = |entries: &str| format!(r#"{{"tables": [{{"name": "T", "entries": [{}]}}]}}"#, entries);
+        let name = || String::from("T");
+        assert_eq!(TableSet::parse(&table("")), Err(TableError::EmptyTable { table: name() }));
+        assert_eq!(
+            TableSet::parse(&table(r#"{"result": "a", "range": [1, 2]}, {"result": "b", "weight": 2}"#)),
+            Err(TableError::MixedEntries { table: name() })
+        );
+        assert_eq!(
+            TableSet::parse(&table(r#"{"result": "a", "range": [3, 1]}"#)),
+            Err(TableError::InvalidRange { table: name(), low: 3, high: 1 })
+        );
+        assert_eq!(
+            TableSet::parse(&table(r#"{"result": "a", "range": [1, 3]}, {"result": "b", "range": [3, 4]}"#)),
+            Err(TableError::OverlappingRanges { table: name(), value: 3 })
+        );
+        assert_eq!(
+            TableSet::parse(&table(r#"{"result": "a", "roll_on": ["Missing"]}"#)),
+            Err(TableError::UnknownTable { table: String::from("Missing") })
+        );
+        assert!(matches!(TableSet::parse("{"), Err(TableError::Json(_))));
+        assert!(matches!(TableSet::parse("[[tables]"), Err(TableError::Toml(_))));
+    }
+
+    #[test]
+    fn gaps_and_loops() {
+        let gap = r#"{"tables": [{"name": "T", "dice": "1d4", "entries": [{"result": "a", "range": [1, 2]}]}]}"#;
+        let roll = TableSet::parse(gap).unwrap().roll_with("T", &mut FixedSequenceRng::new(vec![4]));
+        assert_eq!(roll, Err(TableError::NoEntry { table: String::from("T"), roll: 4 }));
+
+        let looped = r#"{"tables": [{"name": "T", "entries": [{"result": "again", "roll_on": ["T"]}]}]}"#;
+        let roll = TableSet::parse(looped).unwrap().roll("T");
+        assert_eq!(roll, Err(TableError::NestedTooDeeply { max: MAX_TABLE_DEPTH }));
+    }
+}
@@ -1,23 +1,70 @@
 use core::fmt;
 
 use leptos::*;
-use crate::models::dice::DiceRollResult;
+use serde::{Deserialize, Serialize};
+use crate::models::dice::{DiceExpressionResult, TargetOutcome};
+use crate::models::random_table::TableRoll;
 use chrono::{DateTime, Utc};
 
 // Individual entry in the dice roll history
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DiceHistoryEntry {
     pub timestamp: DateTime<Utc>,
-    pub roll_results: Vec<DiceRollResult>,
+    pub roll_results: Vec<DiceExpressionResult>,
+    #[serde(default)]
+    pub description: Option<String>, // What the roll was for, e.g. "Longsword attack"
+    #[serde(default)]
+    pub table: Option<String>, // Name of the random table rolled on, if any
+    #[serde(default)]
+    pub table_result: Option<String>, // The entry the table roll landed on, e.g. "3 goblins"
 }
 
 impl DiceHistoryEntry {
-    pub fn new(roll_results: Vec<DiceRollResult>) -> Self {
+    pub fn new(roll_results: Vec<DiceExpressionResult>) -> Self {
+        // Taken from the results, joining them if several rolls were described.
+        // Repeated rolls all carry the same description, so it's only shown once.
+        let mut descriptions: Vec<&str> = roll_results.iter()
+            .filter_map(|result| result.description.as_deref())
+            .collect();
+        descriptions.dedup();
+        let description = (!descriptions.is_empty()).then(|| descriptions.join("; "));
+
         Self {
             roll_results,
             timestamp: Utc::now(),
+            description,
+            table: None,
+            table_result: None,
+        }
+    }
+
+    // One entry for a whole table roll, including the dice rolled for its text and nested tables
+    pub fn from_table_roll(roll: &TableRoll) -> Self {
+        Self {
+            table: Some(roll.table.clone()),
+            table_result: Some(roll.full_result()),
+            ..Self::new(roll.dice_results())
         }
     }
+
+    // Every inline label across the entry's rolls, e.g. ["STR", "prof"]
+    pub fn labels(&self) -> Vec<&str> {
+        self.roll_results.iter().flat_map(|result| result.labels()).collect()
+    }
+
+    // Pass or fail for each roll that was checked against a target
+    pub fn outcomes(&self) -> Vec<TargetOutcome> {
+        self.roll_results.iter().filter_map(|result| result.outcome).collect()
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.roll_results.iter().any(|result| result.is_critical())
+    }
+
+    pub fn is_fumble(&self) -> bool {
+        self.roll_results.iter().any(|result| result.is_fumble())
+    }
+
     // pub get_timestamp() -> &str {
     //     self.timestamp.convert_to_system_timezone().format("%HH:%M:%S")
     // }
@@ -34,7 +81,7 @@ impl fmt::Display for DiceHistoryEntry {
 }
 
 // Global store for dice roll history
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct DiceHistoryStore {
     history: RwSignal<Vec<DiceHistoryEntry>>,
 }
@@ -46,11 +93,16 @@ impl DiceHistoryStore {
         }
     }
     
-    pub fn add_roll(&self, results: Vec<DiceRollResult>) {
+    pub fn add_roll(&self, results: Vec<DiceExpressionResult>) {
         let entry = DiceHistoryEntry::new(results);
         self.history.update(|h| h.push(entry));
     }
     
+    pub fn add_table_roll(&self, roll: &TableRoll) {
+        let entry = DiceHistoryEntry::from_table_roll(roll);
+        self.history.update(|h| h.push(entry));
+    }
+    
     pub fn clear(&self) {
         self.history.set(Vec::new());
     }
@@ -60,10 +112,16 @@ impl DiceHistoryStore {
     }
 }
 
+impl Default for DiceHistoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Create a context provider for global access
 pub fn provide_dice_history() -> DiceHistoryStore {
     let store = DiceHistoryStore::new();
-    provide_context(store.clone());
+    provide_context(store);
     store
 }
 
@@ -0,0 +1,122 @@
+use std::fmt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// Bump whenever a serialized type changes shape, so newer exports are rejected
+// instead of being misread
+pub const SCHEMA_VERSION: u32 = 2;
+
+// The oldest version still read. Version 2 only added fields that have
+// defaults, such as labels, targets and seeds, so version 1 reads as it is.
+pub const MIN_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerializationError {
+    Json(String),
+    UnsupportedVersion { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Json(message) => write!(f, "Invalid JSON: {}", message),
+            SerializationError::UnsupportedVersion { found, expected } => {
+                write!(f, "Unsupported schema version {} (expected {})", found, expected)
+            },
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+// The JSON envelope around everything we export: {"version": 2, "data": ...}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(data: T) -> Self {
+        Self { version: SCHEMA_VERSION, data }
+    }
+}
+
+// Just the version, read first so a newer export fails with a clear error
+// rather than whatever field it happens to trip over
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+pub fn to_json<T: Serialize>(data: &T) -> Result<String, SerializationError> {
+    serde_json::to_string(&Versioned::new(data)).map_err(|err| SerializationError::Json(err.to_string()))
+}
+
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, SerializationError> {
+    let VersionOnly { version } = serde_json::from_str(json)
+        .map_err(|err| SerializationError::Json(err.to_string()))?;
+    if !(MIN_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version) {
+        return Err(SerializationError::UnsupportedVersion { found: version, expected: SCHEMA_VERSION });
+    }
+
+    let envelope: Versioned<T> = serde_json::from_str(json)
+        .map_err(|err| SerializationError::Json(err.to_string()))?;
+    Ok(envelope.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::dice::{DiceExpression, DiceExpressionResult, DiceRoll, FixedSequenceRng};
+
+    #[test]
+    fn expressions_and_results_round_trip() {
+        let expression = DiceExpression::from_expression("2d20kh1+5[atk] vs AC 15 # Attack").unwrap();
+        assert_eq!(from_json::<DiceExpression>(&to_json(&expression).unwrap()), Ok(expression.clone()));
+
+        let result = expression.roll_with(&mut FixedSequenceRng::new(vec![4, 17])).unwrap();
+        assert_eq!(from_json(&to_json(&result).unwrap()), Ok(result));
+    }
+
+    #[test]
+    fn stored_rolls_are_checked() {
+        let json = r#"{"version": 1, "data": {"die": {"faces": {"numbered": 6}}, "die_count": 2, "operations": [{"keep_highest": 3}], "modifier": null}}"#;
+        assert!(matches!(from_json::<DiceRoll>(json), Err(SerializationError::Json(_))));
+        let json = r#"{"version": 3, "data": null}"#;
+        assert_eq!(from_json::<DiceRoll>(json), Err(SerializationError::UnsupportedVersion { found: 3, expected: 2 }));
+        let json = r#"{"version": 0, "data": null}"#;
+        assert_eq!(from_json::<DiceRoll>(json), Err(SerializationError::UnsupportedVersion { found: 0, expected: 2 }));
+    }
+
+    #[test]
+    fn version_1_exports_still_read() {
+        // "2d20kh1+1d6!-3" and a roll of it, as version 1 wrote them
+        let json = concat!(
+            r#"{"version":1,"data":{"root":{"binary":{"op":"subtract","lhs":{"binary":{"op":"add","#,
+            r#""lhs":{"roll":{"die":{"faces":{"numbered":20}},"die_count":2,"operations":[{"keep_highest":1}],"#,
+            r#""modifier":null}},"rhs":{"roll":{"die":{"faces":{"numbered":6}},"die_count":1,"#,
+            r#""operations":[{"explode":{"kind":"standard","threshold":null}}],"modifier":null}}}},"#,
+            r#""rhs":{"constant":3}}}}}"#,
+        );
+        let expression = from_json::<DiceExpression>(json).unwrap();
+        assert_eq!(expression, DiceExpression::from_expression("2d20kh1+1d6!-3").unwrap());
+
+        let json = concat!(
+            r#"{"version":1,"data":{"result":22,"root":{"binary":{"op":"subtract","lhs":{"binary":{"op":"add","#,
+            r#""lhs":{"roll":{"result":17,"dice_results":[{"die":{"faces":{"numbered":20}},"result":4,"keep":false,"#,
+            r#""explosion":false,"compounded":[],"rerolled":[],"clamped_from":null,"success":false,"failure":false,"#,
+            r#""critical":false,"fumble":false},{"die":{"faces":{"numbered":20}},"result":17,"keep":true,"#,
+            r#""explosion":false,"compounded":[],"rerolled":[],"clamped_from":null,"success":false,"failure":false,"#,
+            r#""critical":false,"fumble":false}],"operations":[{"keep_highest":1}],"modifier":null,"seed":null}},"#,
+            r#""rhs":{"roll":{"result":8,"dice_results":[{"die":{"faces":{"numbered":6}},"result":6,"keep":true,"#,
+            r#""explosion":false,"compounded":[],"rerolled":[],"clamped_from":null,"success":false,"failure":false,"#,
+            r#""critical":false,"fumble":false},{"die":{"faces":{"numbered":6}},"result":2,"keep":true,"#,
+            r#""explosion":true,"compounded":[],"rerolled":[],"clamped_from":null,"success":false,"failure":false,"#,
+            r#""critical":false,"fumble":false}],"operations":[{"explode":{"kind":"standard","threshold":null}}],"#,
+            r#""modifier":null,"seed":null}},"value":25}},"rhs":{"constant":3},"value":22}},"seed":null}}"#,
+        );
+        let result = from_json::<DiceExpressionResult>(json).unwrap();
+        assert_eq!((result.result, result.seed, result.target), (22, None, None));
+    }
+}
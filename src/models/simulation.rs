@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::models::dice::{DiceError, DiceExpression, SeededDiceRng};
+use crate::models::probability::Distribution;
+
+// z-score for a two-sided 95% confidence interval
+const Z_95: f64 = 1.96;
+
+// An estimate together with its 95% confidence bounds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl ConfidenceInterval {
+    // Half the width of the interval, the "± x" part
+    pub fn margin(&self) -> f64 {
+        (self.high - self.low) / 2.0
+    }
+}
+
+impl fmt::Display for ConfidenceInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} ± {:.2}", self.estimate, self.margin())
+    }
+}
+
+// Estimates the odds of an expression by rolling it many times with a seeded
+// generator. Runs can be added in chunks, so the page can stay responsive.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub expression: DiceExpression,
+    pub seed: u64,
+    rng: SeededDiceRng,
+    counts: BTreeMap<i32, u64>,
+    runs: u64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl Simulation {
+    // Like the exact distribution, this gives the odds of a single repetition,
+    // so "6x 4d6dl1" is simulated as "4d6dl1"
+    pub fn new(expression: DiceExpression, seed: u64) -> Self {
+        Self {
+            expression: DiceExpression { repeat: None, ..expression },
+            seed,
+            rng: SeededDiceRng::new(seed),
+            counts: BTreeMap::new(),
+            runs: 0,
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        }
+    }
+
+    // Rolls the expression `runs` more times
+    pub fn run(&mut self, runs: u64) -> Result<(), DiceError> {
+        for _ in 0..runs {
+            let result = self.expression.roll_with(&mut self.rng)?.result;
+            *self.counts.entry(result).or_insert(0) += 1;
+            self.runs += 1;
+            self.sum += f64::from(result);
+            self.sum_of_squares += f64::from(result).powi(2);
+        }
+        Ok(())
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    // Observed frequency of each total
+    pub fn distribution(&self) -> Distribution {
+        Distribution::from_counts(&self.counts)
+    }
+
+    // Sample mean with a normal-approximation interval
+    pub fn mean(&self) -> ConfidenceInterval {
+        if self.runs == 0 {
+            return ConfidenceInterval { estimate: 0.0, low: 0.0, high: 0.0 };
+        }
+        let n = self.runs as f64;
+        let mean = self.sum / n;
+        let variance = if self.runs > 1 {
+            ((self.sum_of_squares - n * mean * mean) / (n - 1.0)).max(0.0)
+        } else {
+            0.0
+        };
+        let margin = Z_95 * (variance / n).sqrt();
+        ConfidenceInterval { estimate: mean, low: mean - margin, high: mean + margin }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.distribution().std_dev()
+    }
+
+    // P(result >= value), with a Wilson score interval so it stays sensible near 0% and 100%
+    pub fn at_least(&self, value: i32) -> ConfidenceInterval {
+        let hits: u64 = self.counts.range(value..).map(|(_, count)| count).sum();
+        wilson_interval(hits, self.runs)
+    }
+}
+
+fn wilson_interval(hits: u64, runs: u64) -> ConfidenceInterval {
+    if runs == 0 {
+        return ConfidenceInterval { estimate: 0.0, low: 0.0, high: 1.0 };
+    }
+    let n = runs as f64;
+    let p = hits as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ConfidenceInterval {
+        estimate: p,
+        low: (center - margin).max(0.0),
+        high: (center + margin).min(1.0),
+    }
+}
+
+// Runs the whole simulation in one go
+pub fn simulate(expression: &DiceExpression, runs: u64, seed: u64) -> Result<Simulation, DiceError> {
+    let mut simulation = Simulation::new(expression.clone(), seed);
+    simulation.run(runs)?;
+    Ok(simulation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_simulations_repeat() {
+        let expression = DiceExpression::from_expression("4d6dl1").unwrap();
+        let first = simulate(&expression, 1_000, 9).unwrap();
+        let second = simulate(&expression, 1_000, 9).unwrap();
+        assert_eq!(first.distribution(), second.distribution());
+        assert_eq!(first.runs(), 1_000);
+    }
+
+    #[test]
+    fn repeated_expressions_simulate_one_repetition() {
+        let repeated = DiceExpression::from_expression("6x 4d6dl1").unwrap();
+        let single = DiceExpression::from_expression("4d6dl1").unwrap();
+        assert_eq!(simulate(&repeated, 100, 3).unwrap().distribution(), simulate(&single, 100, 3).unwrap().distribution());
+    }
+
+    #[test]
+    fn estimates_are_close_to_the_exact_odds() {
+        let expression = DiceExpression::from_expression("2d6").unwrap();
+        let simulation = simulate(&expression, 20_000, 1).unwrap();
+        let mean = simulation.mean();
+        assert!(mean.low < 7.0 && 7.0 < mean.high, "{}", mean);
+        let odds = simulation.at_least(7);
+        assert!(odds.low < 21.0 / 36.0 && 21.0 / 36.0 < odds.high);
+    }
+
+    #[test]
+    fn wilson_intervals_stay_within_bounds() {
+        let none = wilson_interval(0, 100);
+        assert_eq!(none.estimate, 0.0);
+        assert_eq!(none.low, 0.0);
+        assert!(none.high > 0.0 && none.high < 0.1);
+        let all = wilson_interval(100, 100);
+        assert!(all.low > 0.9 && all.high <= 1.0);
+        assert_eq!(wilson_interval(0, 0).high, 1.0);
+    }
+}
@@ -0,0 +1,48 @@
+use leptos::*;
+use crate::models::dice::Variables;
+
+// Global store for the "@name" variables expressions can refer to
+#[derive(Clone, Copy)]
+pub struct VariableStore {
+    variables: RwSignal<Variables>,
+}
+
+impl VariableStore {
+    pub fn new() -> Self {
+        Self {
+            variables: create_rw_signal(Variables::new()),
+        }
+    }
+
+    pub fn set_variable(&self, name: &str, value: i32) {
+        self.variables.update(|variables| variables.set(name, value));
+    }
+
+    pub fn remove_variable(&self, name: &str) {
+        self.variables.update(|variables| {
+            variables.remove(name);
+        });
+    }
+
+    pub fn get_variables(&self) -> ReadSignal<Variables> {
+        self.variables.read_only()
+    }
+}
+
+impl Default for VariableStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Create a context provider for global access
+pub fn provide_variables() -> VariableStore {
+    let store = VariableStore::new();
+    provide_context(store);
+    store
+}
+
+// Helper function to use the variable store from any component
+pub fn use_variables() -> VariableStore {
+    use_context::<VariableStore>().expect("No VariableStore has been provided")
+}